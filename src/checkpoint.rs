@@ -0,0 +1,415 @@
+//! Checkpoint files for resuming interrupted bulk runs
+//!
+//! A checkpoint is a JSON Lines file. The first line identifies the input the
+//! run was started with; every following line records one completed lookup
+//! together with its position in the input:
+//!
+//! ```text
+//! {"reverdns_checkpoint":1,"fingerprint":"9f3a61c2b0e4d857"}
+//! {"index":0,"result":{"ip":"8.8.8.8","hostname":"dns.google",...}}
+//! ```
+//!
+//! Each record is flushed to the operating system as soon as it is written
+//! and synced to disk periodically, so a killed process loses nothing and a
+//! host crash loses at most the last [`SYNC_INTERVAL`] records.
+
+use crate::dns::LookupResult;
+use crate::error::{Result, ReverDNSError};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// Checkpoint format version
+const FORMAT_VERSION: u32 = 1;
+
+/// Number of records between `fsync` calls
+pub const SYNC_INTERVAL: usize = 256;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    reverdns_checkpoint: u32,
+    fingerprint: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Record {
+    index: u64,
+    result: LookupResult,
+}
+
+/// Set of input positions that already have a result
+#[derive(Debug, Clone, Default)]
+pub struct CompletedSet {
+    bits: Vec<u64>,
+    len: usize,
+}
+
+impl CompletedSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark an input position as completed
+    pub fn insert(&mut self, index: u64) {
+        let (word, bit) = ((index / 64) as usize, index % 64);
+        if word >= self.bits.len() {
+            self.bits.resize(word + 1, 0);
+        }
+        if self.bits[word] & (1 << bit) == 0 {
+            self.bits[word] |= 1 << bit;
+            self.len += 1;
+        }
+    }
+
+    /// Whether an input position is completed
+    pub fn contains(&self, index: u64) -> bool {
+        self.bits
+            .get((index / 64) as usize)
+            .is_some_and(|word| word & (1 << (index % 64)) != 0)
+    }
+
+    /// Number of completed positions
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Append-only writer for a checkpoint file
+pub struct Checkpoint {
+    path: PathBuf,
+    out: BufWriter<File>,
+    unsynced: usize,
+}
+
+impl Checkpoint {
+    /// Start a new checkpoint. Fails if `path` already holds one so a previous
+    /// run is never discarded by accident.
+    pub fn create(path: &Path, fingerprint: &str) -> Result<Self> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::AlreadyExists => ReverDNSError::ConfigError(format!(
+                    "Checkpoint {} already exists; pass --resume to continue it or delete it",
+                    path.display()
+                )),
+                _ => e.into(),
+            })?;
+
+        let mut checkpoint = Self {
+            path: path.to_path_buf(),
+            out: BufWriter::new(file),
+            unsynced: 0,
+        };
+        let header = Header {
+            reverdns_checkpoint: FORMAT_VERSION,
+            fingerprint: fingerprint.to_string(),
+        };
+        serde_json::to_writer(&mut checkpoint.out, &header)?;
+        checkpoint.out.write_all(b"\n")?;
+        checkpoint.sync()?;
+        Ok(checkpoint)
+    }
+
+    /// Reopen an existing checkpoint for appending and return the positions
+    /// it has already completed. A missing file starts a new checkpoint.
+    ///
+    /// A partially written final line (from a crash mid-write) is discarded.
+    pub fn resume(path: &Path, fingerprint: &str) -> Result<(Self, CompletedSet)> {
+        if !path.exists() {
+            return Ok((Self::create(path, fingerprint)?, CompletedSet::new()));
+        }
+
+        let mut completed = CompletedSet::new();
+        let mut valid_len = 0u64;
+        let mut reader = io::BufReader::new(File::open(path)?);
+        let mut line = String::new();
+        let mut line_no = 0usize;
+
+        loop {
+            line.clear();
+            let read = reader.read_line(&mut line)?;
+            if read == 0 {
+                break;
+            }
+            line_no += 1;
+
+            if !line.ends_with('\n') {
+                warn!(
+                    "{}:{}: discarding incomplete checkpoint record",
+                    path.display(),
+                    line_no
+                );
+                break;
+            }
+
+            if line_no == 1 {
+                let header: Header = serde_json::from_str(&line).map_err(|e| {
+                    ReverDNSError::ConfigError(format!(
+                        "{}: not a reverdns checkpoint: {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+                if header.reverdns_checkpoint != FORMAT_VERSION {
+                    return Err(ReverDNSError::ConfigError(format!(
+                        "{}: unsupported checkpoint version {}",
+                        path.display(),
+                        header.reverdns_checkpoint
+                    )));
+                }
+                if header.fingerprint != fingerprint {
+                    return Err(ReverDNSError::ConfigError(format!(
+                        "{}: checkpoint was created for a different input",
+                        path.display()
+                    )));
+                }
+            } else {
+                let record: Record = serde_json::from_str(&line).map_err(|e| {
+                    ReverDNSError::ConfigError(format!("{}:{}: {}", path.display(), line_no, e))
+                })?;
+                completed.insert(record.index);
+            }
+
+            valid_len += read as u64;
+        }
+
+        if line_no == 0 {
+            // Empty file: treat like a new checkpoint
+            fs::remove_file(path)?;
+            return Ok((Self::create(path, fingerprint)?, completed));
+        }
+
+        let file = OpenOptions::new().write(true).open(path)?;
+        file.set_len(valid_len)?;
+        let file = OpenOptions::new().append(true).open(path)?;
+        debug!(
+            "Resuming checkpoint {} with {} completed lookups",
+            path.display(),
+            completed.len()
+        );
+
+        Ok((
+            Self {
+                path: path.to_path_buf(),
+                out: BufWriter::new(file),
+                unsynced: 0,
+            },
+            completed,
+        ))
+    }
+
+    /// Stream every result stored in a checkpoint, in the order recorded
+    pub fn replay<F>(path: &Path, mut f: F) -> Result<()>
+    where
+        F: FnMut(LookupResult) -> Result<()>,
+    {
+        let reader = io::BufReader::new(File::open(path)?);
+        for line in reader.lines().skip(1) {
+            let line = line?;
+            match serde_json::from_str::<Record>(&line) {
+                Ok(record) => f(record.result)?,
+                // Only a torn final line can fail here; `resume` removed it
+                Err(_) => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// Durably record a completed lookup
+    pub fn record(&mut self, index: u64, result: &LookupResult) -> Result<()> {
+        serde_json::to_writer(
+            &mut self.out,
+            &Record {
+                index,
+                result: result.clone(),
+            },
+        )?;
+        self.out.write_all(b"\n")?;
+        self.out.flush()?;
+
+        self.unsynced += 1;
+        if self.unsynced >= SYNC_INTERVAL {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Flush buffered records and sync them to disk
+    pub fn sync(&mut self) -> Result<()> {
+        self.out.flush()?;
+        self.out.get_ref().sync_data()?;
+        self.unsynced = 0;
+        Ok(())
+    }
+
+    /// Path of the checkpoint file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Remove the checkpoint after a run has completed
+    pub fn remove(self) -> Result<()> {
+        let path = self.path.clone();
+        drop(self);
+        fs::remove_file(path)?;
+        Ok(())
+    }
+}
+
+/// Stable fingerprint of a run's input (FNV-1a over the given parts)
+pub fn fingerprint<I, S>(parts: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<[u8]>,
+{
+    let mut hash = Fnv1a::new();
+    for part in parts {
+        hash.update(part.as_ref());
+        hash.update(&[0]);
+    }
+    hash.to_string()
+}
+
+/// Fingerprint of a file's contents, read in chunks so large inputs are not
+/// held in memory
+pub fn file_fingerprint(path: &Path) -> Result<String> {
+    let mut reader = io::BufReader::new(File::open(path)?);
+    let mut hash = Fnv1a::new();
+    loop {
+        let chunk = reader.fill_buf()?;
+        if chunk.is_empty() {
+            return Ok(hash.to_string());
+        }
+        hash.update(chunk);
+        let len = chunk.len();
+        reader.consume(len);
+    }
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` is stable across releases
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+impl std::fmt::Display for Fnv1a {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::LookupStatus;
+    use tempfile::TempDir;
+
+    fn result(ip: &str) -> LookupResult {
+        LookupResult {
+            ttl: Some(300),
+            latency_ms: 5,
+            ..LookupResult::new(ip, LookupStatus::Success, "test").with_hostnames(["host.example"])
+        }
+    }
+
+    #[test]
+    fn test_completed_set() {
+        let mut set = CompletedSet::new();
+        set.insert(3);
+        set.insert(200);
+        set.insert(3);
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(3));
+        assert!(set.contains(200));
+        assert!(!set.contains(4));
+        assert!(!set.contains(10_000));
+    }
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("run.ckpt");
+
+        let mut checkpoint = Checkpoint::create(&path, "abc").unwrap();
+        checkpoint.record(0, &result("192.0.2.1")).unwrap();
+        checkpoint.record(2, &result("192.0.2.3")).unwrap();
+        drop(checkpoint);
+
+        // Refuse to overwrite without resume
+        assert!(Checkpoint::create(&path, "abc").is_err());
+        // Refuse to resume a different input
+        assert!(Checkpoint::resume(&path, "other").is_err());
+
+        let (mut checkpoint, completed) = Checkpoint::resume(&path, "abc").unwrap();
+        assert_eq!(completed.len(), 2);
+        assert!(completed.contains(0) && completed.contains(2));
+        checkpoint.record(1, &result("192.0.2.2")).unwrap();
+        drop(checkpoint);
+
+        let mut ips = Vec::new();
+        Checkpoint::replay(&path, |r| {
+            ips.push(r.ip);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(ips, vec!["192.0.2.1", "192.0.2.3", "192.0.2.2"]);
+    }
+
+    #[test]
+    fn test_resume_discards_torn_record() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("run.ckpt");
+
+        let mut checkpoint = Checkpoint::create(&path, "abc").unwrap();
+        checkpoint.record(0, &result("192.0.2.1")).unwrap();
+        drop(checkpoint);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"index\":1,\"res").unwrap();
+        drop(file);
+
+        let (mut checkpoint, completed) = Checkpoint::resume(&path, "abc").unwrap();
+        assert_eq!(completed.len(), 1);
+        checkpoint.record(1, &result("192.0.2.2")).unwrap();
+        drop(checkpoint);
+
+        let (_, completed) = Checkpoint::resume(&path, "abc").unwrap();
+        assert_eq!(completed.len(), 2);
+    }
+
+    #[test]
+    fn test_fingerprint_is_stable() {
+        assert_eq!(fingerprint(["a", "b"]), fingerprint(["a", "b"]));
+        assert_ne!(fingerprint(["ab"]), fingerprint(["a", "b"]));
+    }
+
+    #[test]
+    fn test_file_fingerprint_tracks_content() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("ips.txt");
+        fs::write(&path, "10.0.0.1\n").unwrap();
+        let before = file_fingerprint(&path).unwrap();
+        assert_eq!(file_fingerprint(&path).unwrap(), before);
+
+        // Same length, different addresses
+        fs::write(&path, "10.0.0.2\n").unwrap();
+        assert_ne!(file_fingerprint(&path).unwrap(), before);
+    }
+}
@@ -1,11 +1,32 @@
-use clap::{Parser, ValueEnum};
+use crate::dns::{
+    DohMethod, HealthPolicy, Jitter, LookupCache, LookupStatus, RetryPolicy, Strategy,
+    SystemResolvers, TransportMode,
+};
+use crate::output::CsvHostnames;
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
+use std::net::IpAddr;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::time::Duration;
 
-#[derive(Debug, Clone, Copy, ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum OutputFormat {
     #[value(name = "json")]
     Json,
     #[value(name = "csv")]
     Csv,
+    #[value(name = "ndjson")]
+    Ndjson,
+    #[value(name = "sqlite")]
+    Sqlite,
+    #[value(name = "hosts")]
+    Hosts,
+    #[value(name = "zone")]
+    Zone,
+    #[value(name = "text")]
+    Text,
 }
 
 impl std::fmt::Display for OutputFormat {
@@ -13,11 +34,17 @@ impl std::fmt::Display for OutputFormat {
         match self {
             OutputFormat::Json => write!(f, "json"),
             OutputFormat::Csv => write!(f, "csv"),
+            OutputFormat::Ndjson => write!(f, "ndjson"),
+            OutputFormat::Sqlite => write!(f, "sqlite"),
+            OutputFormat::Hosts => write!(f, "hosts"),
+            OutputFormat::Zone => write!(f, "zone"),
+            OutputFormat::Text => write!(f, "text"),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum LogLevel {
     #[value(name = "trace")]
     Trace,
@@ -43,64 +70,328 @@ impl std::fmt::Display for LogLevel {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[value(name = "text")]
+    Text,
+    #[value(name = "json")]
+    Json,
+}
+
+impl std::fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogFormat::Text => write!(f, "text"),
+            LogFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
 /// ReverDNS - High-performance reverse DNS lookup tool
 #[derive(Parser, Debug)]
 #[command(name = "reverdns")]
 #[command(version = "2.0.0")]
 #[command(about = "High-performance reverse DNS (PTR) lookup tool for bulk IP lists", long_about = None)]
 pub struct Args {
-    /// IP addresses to lookup (can be provided as arguments or via --input)
+    /// IP addresses, CIDR prefixes (10.0.0.0/24), ranges (10.0.0.5-10.0.0.40)
+    /// or wildcards (192.168.1.*) to lookup (can also be provided via --input)
     #[arg(value_name = "IPS")]
     pub ips: Vec<String>,
 
-    /// Input file with IP addresses (one per line)
+    /// Input file with IP addresses or ranges (one per line)
     #[arg(short, long, value_name = "FILE")]
     pub input: Option<String>,
 
+    /// Allow ranges larger than 65536 addresses
+    #[arg(long)]
+    pub allow_large_ranges: bool,
+
+    /// Record completed lookups in FILE so an interrupted run can be resumed
+    #[arg(long, value_name = "FILE")]
+    pub checkpoint: Option<PathBuf>,
+
+    /// Resume from an existing --checkpoint, skipping completed addresses.
+    /// csv, ndjson, text and hosts output files are appended to, and sqlite
+    /// continues the same run; json and zone output is written again from
+    /// the checkpoint, as those are only valid whole. On stdout only new
+    /// results are written, except for json and zone.
+    #[arg(long, requires = "checkpoint")]
+    pub resume: bool,
+
     /// Output file path (default: stdout)
     #[arg(short, long, value_name = "FILE")]
     pub output: Option<String>,
 
-    /// Output format: json, csv
-    #[arg(short, long, value_enum, default_value = "json")]
+    /// Output format: json, csv, ndjson (one JSON result per line), sqlite
+    /// (a database at --output), hosts (/etc/hosts entries), zone (BIND
+    /// reverse zones), text (ip<TAB>hostname)
+    #[arg(short, long, value_enum, default_value = "json", env = "REVERDNS_FORMAT")]
     pub format: OutputFormat,
 
-    /// Custom DNS resolver IP (can be used multiple times)
-    #[arg(short, long, value_name = "RESOLVER")]
+    /// CSV layout for addresses with several PTR names: joined (extra
+    /// hostnames column) or rows (one row per name)
+    #[arg(
+        long,
+        value_enum,
+        value_name = "LAYOUT",
+        default_value = "joined",
+        env = "REVERDNS_CSV_HOSTNAMES"
+    )]
+    pub csv_hostnames: CsvHostnames,
+
+    /// End ndjson output with a {"metadata": ...} line summarising the run
+    #[arg(long)]
+    pub ndjson_summary: bool,
+
+    /// Custom DNS resolver IP, optionally with a port (can be used multiple
+    /// times): 127.0.0.1:5353, [2001:db8::1]:5300. Per-server limits and
+    /// transport follow an @: 8.8.8.8@50qps/max=20/transport=tcp
+    #[arg(
+        short,
+        long,
+        value_name = "RESOLVER",
+        env = "REVERDNS_RESOLVERS",
+        value_delimiter = ','
+    )]
     pub resolver: Vec<String>,
 
+    /// Use the name servers, search domains and options of /etc/resolv.conf
+    /// (alongside any other resolvers)
+    #[arg(long)]
+    pub system_resolvers: bool,
+
+    /// /etc/resolv.conf as read for --system-resolvers (see
+    /// [`crate::config::apply_system_resolvers`])
+    #[arg(skip)]
+    pub system_conf: Option<SystemResolvers>,
+
+    /// DNS-over-TLS resolver (can be used multiple times), with the name its
+    /// certificate must match: 1.1.1.1#cloudflare-dns.com or
+    /// [2620:fe::fe]:853#dns.quad9.net@20qps
+    #[arg(
+        long,
+        value_name = "HOST[:PORT]#TLS_NAME",
+        env = "REVERDNS_DOT_RESOLVERS",
+        value_delimiter = ','
+    )]
+    pub dot_resolver: Vec<String>,
+
+    /// Transport for plain DNS resolvers that do not set their own
+    #[arg(
+        long,
+        value_enum,
+        default_value = "udp",
+        env = "REVERDNS_TRANSPORT"
+    )]
+    pub transport: TransportMode,
+
+    /// How queries are spread over resolvers
+    #[arg(
+        long,
+        value_enum,
+        default_value = "weighted",
+        env = "REVERDNS_STRATEGY"
+    )]
+    pub strategy: Strategy,
+
+    /// Confirm each PTR name with A/AAAA lookups (forward-confirmed reverse
+    /// DNS) and report whether it resolves back to the address
+    #[arg(long)]
+    pub verify_forward: bool,
+
+    /// Take a resolver out of rotation after this many consecutive failures
+    /// (0 disables)
+    #[arg(
+        long,
+        value_name = "NUM",
+        default_value = "5",
+        env = "REVERDNS_EJECT_AFTER"
+    )]
+    pub eject_after: u32,
+
+    /// Seconds an ejected resolver sits out before it is probed again
+    /// (doubles after each failed probe, up to 10x)
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value = "30",
+        env = "REVERDNS_EJECT_COOLDOWN"
+    )]
+    pub eject_cooldown: u64,
+
     /// Timeout per lookup in seconds
-    #[arg(short, long, value_name = "SECONDS", default_value = "5")]
+    #[arg(
+        short,
+        long,
+        value_name = "SECONDS",
+        default_value = "5",
+        env = "REVERDNS_TIMEOUT"
+    )]
     pub timeout: u64,
 
     /// Number of concurrent lookups
-    #[arg(short, long, value_name = "NUM", default_value = "10")]
+    #[arg(
+        short,
+        long,
+        value_name = "NUM",
+        default_value = "10",
+        env = "REVERDNS_CONCURRENCY"
+    )]
     pub concurrency: usize,
 
     /// Rate limit: lookups per second
-    #[arg(short = 'l', long, value_name = "PER_SEC", default_value = "100")]
+    #[arg(
+        short = 'l',
+        long,
+        value_name = "PER_SEC",
+        default_value = "100",
+        env = "REVERDNS_RATE_LIMIT"
+    )]
     pub rate_limit: u32,
 
+    /// Lookups allowed back-to-back before the rate limit applies
+    /// (default: 1, i.e. evenly spaced)
+    #[arg(long, value_name = "NUM", env = "REVERDNS_RATE_BURST")]
+    pub rate_burst: Option<u32>,
+
+    /// Give up on a lookup (status rate_limited) if it would wait longer
+    /// than this for the rate limiter (default: wait as long as needed)
+    #[arg(long, value_name = "SECONDS", env = "REVERDNS_RATE_LIMIT_WAIT")]
+    pub rate_limit_wait: Option<u64>,
+
+    /// Cache results in memory, so repeated addresses are answered without
+    /// a query while their TTL lasts
+    #[arg(long)]
+    pub cache: bool,
+
+    /// Longest time in seconds a result is cached, whatever its TTL
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value = "3600",
+        env = "REVERDNS_CACHE_TTL"
+    )]
+    pub cache_ttl: u64,
+
+    /// Most results kept in the cache; the least recently used are evicted
+    #[arg(
+        long,
+        value_name = "NUM",
+        default_value = "10000",
+        env = "REVERDNS_CACHE_MAX_SIZE"
+    )]
+    pub cache_max_size: usize,
+
+    /// Keep results in an SQLite database at PATH and reuse them across
+    /// runs while their TTL lasts
+    #[arg(long, value_name = "PATH", env = "REVERDNS_CACHE_DB", global = true)]
+    pub cache_db: Option<PathBuf>,
+
+    /// Reuse results from --cache-db until they are this many seconds old,
+    /// whatever their TTL
+    #[arg(long, value_name = "SECONDS", env = "REVERDNS_MAX_AGE", global = true)]
+    pub max_age: Option<u64>,
+
     /// Use DNS-over-HTTPS (DoH)
     #[arg(long)]
     pub dns_over_https: bool,
 
-    /// Custom DoH provider URL
-    #[arg(long, value_name = "URL")]
-    pub doh_provider: Option<String>,
+    /// Custom DoH provider URL (can be used multiple times), optionally
+    /// with bootstrap addresses and options:
+    /// https://dns.example/dns-query@203.0.113.5/method=get
+    #[arg(
+        long,
+        value_name = "URL",
+        env = "REVERDNS_DOH_PROVIDER",
+        value_delimiter = ','
+    )]
+    pub doh_provider: Vec<String>,
+
+    /// HTTP method for DoH queries: get, post
+    #[arg(
+        long,
+        value_enum,
+        default_value = "post",
+        env = "REVERDNS_DOH_METHOD"
+    )]
+    pub doh_method: DohMethod,
+
+    /// PEM file of CA certificates to trust for DoT and DoH instead of the
+    /// built-in root store
+    #[arg(long, value_name = "PATH", env = "REVERDNS_CA_FILE")]
+    pub ca_file: Option<PathBuf>,
+
+    /// Validate PTR answers with DNSSEC and mark each result secure,
+    /// insecure, bogus or indeterminate
+    #[arg(long)]
+    pub validate_dnssec: bool,
+
+    /// Report answers that fail DNSSEC validation as failed lookups
+    #[arg(long, requires = "validate_dnssec")]
+    pub reject_bogus: bool,
 
     /// Number of retries on failure
-    #[arg(long, value_name = "NUM", default_value = "3")]
+    #[arg(
+        long,
+        value_name = "NUM",
+        default_value = "3",
+        env = "REVERDNS_RETRY_COUNT"
+    )]
     pub retry_count: u32,
 
     /// Initial backoff in milliseconds
-    #[arg(long, value_name = "MS", default_value = "100")]
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value = "100",
+        env = "REVERDNS_RETRY_BACKOFF"
+    )]
     pub retry_backoff: u64,
 
+    /// Longest wait between retries in milliseconds; the backoff doubles
+    /// with each retry up to this
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value = "5000",
+        env = "REVERDNS_RETRY_MAX_BACKOFF"
+    )]
+    pub retry_max_backoff: u64,
+
+    /// Randomisation of the backoff: none, full, decorrelated
+    #[arg(
+        long,
+        value_enum,
+        default_value = "full",
+        env = "REVERDNS_RETRY_JITTER"
+    )]
+    pub retry_jitter: Jitter,
+
+    /// Statuses that are retried: timeout, network_error, servfail,
+    /// refused, failed, nxdomain, nodata, rate_limited
+    #[arg(
+        long,
+        value_enum,
+        value_name = "STATUS",
+        default_value = "timeout,network_error,servfail,refused,failed",
+        env = "REVERDNS_RETRY_ON",
+        value_delimiter = ','
+    )]
+    pub retry_on: Vec<LookupStatus>,
+
+    /// Send retries to the resolver that failed instead of another one
+    #[arg(long)]
+    pub retry_same_resolver: bool,
+
     /// Log level: trace, debug, info, warn, error
-    #[arg(long, value_enum, default_value = "info")]
+    #[arg(long, value_enum, default_value = "info", env = "REVERDNS_LOG_LEVEL")]
     pub log_level: LogLevel,
 
+    /// Log format: text, json
+    #[arg(long, value_enum, default_value = "text", env = "REVERDNS_LOG_FORMAT")]
+    pub log_format: LogFormat,
+
     /// Print statistics after completion
     #[arg(long)]
     pub stats: bool,
@@ -110,19 +401,129 @@ pub struct Args {
     pub web_server: bool,
 
     /// Web server port
-    #[arg(long, value_name = "PORT", default_value = "8080")]
+    #[arg(
+        long,
+        value_name = "PORT",
+        default_value = "8080",
+        env = "REVERDNS_WEB_PORT"
+    )]
     pub web_port: u16,
+
+    /// Web server bind address; the API has no authentication, so only
+    /// expose it beyond localhost behind something that does
+    #[arg(
+        long,
+        value_name = "ADDR",
+        default_value = "127.0.0.1",
+        env = "REVERDNS_WEB_BIND"
+    )]
+    pub web_bind: IpAddr,
+
+    /// Configuration file (default: ./.reverdns.toml or
+    /// $XDG_CONFIG_HOME/reverdns/config.toml)
+    #[arg(long, value_name = "PATH", env = "REVERDNS_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Ignore configuration files
+    #[arg(long, conflicts_with = "config")]
+    pub no_config: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Inspect or maintain the --cache-db database
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+pub enum CacheAction {
+    /// Count stored results, and how many are still reused
+    Stats,
+    /// Delete results that are no longer reused
+    Prune,
+    /// Write every stored result as JSON Lines
+    Export {
+        /// Output file (default: stdout)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
 }
 
 impl Args {
+    /// Maximum number of addresses a single range may expand to
+    pub fn range_limit(&self) -> Option<u128> {
+        if self.allow_large_ranges {
+            None
+        } else {
+            Some(crate::input::DEFAULT_MAX_RANGE_SIZE)
+        }
+    }
+
+    /// Circuit-breaker settings for upstream resolvers
+    pub fn health_policy(&self) -> HealthPolicy {
+        let cooldown = Duration::from_secs(self.eject_cooldown);
+        HealthPolicy {
+            failure_threshold: self.eject_after,
+            cooldown,
+            max_cooldown: cooldown * 10,
+        }
+    }
+
+    /// In-memory result cache, if enabled
+    pub fn lookup_cache(&self) -> Option<LookupCache> {
+        let capacity = NonZeroUsize::new(self.cache_max_size).filter(|_| self.cache)?;
+        Some(LookupCache::new(
+            capacity,
+            Duration::from_secs(self.cache_ttl),
+        ))
+    }
+
+    /// Longest age of a reused --cache-db result, if it replaces the TTL
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age.map(Duration::from_secs)
+    }
+
+    /// When and how failed lookups are retried
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_retries: self.retry_count,
+            base: Duration::from_millis(self.retry_backoff),
+            max_backoff: Duration::from_millis(self.retry_max_backoff.max(self.retry_backoff)),
+            jitter: self.retry_jitter,
+            retry_on: self.retry_on.clone(),
+            switch_resolver: !self.retry_same_resolver,
+        }
+    }
+
     /// Validate arguments
     pub fn validate(&self) -> crate::error::Result<()> {
-        if self.ips.is_empty() && self.input.is_none() {
+        if let Some(Command::Cache { .. }) = self.command {
+            if self.cache_db.is_none() {
+                return Err(crate::error::ReverDNSError::ConfigError(
+                    "The cache command needs --cache-db".to_string(),
+                ));
+            }
+            return Ok(());
+        }
+
+        if !self.web_server && self.ips.is_empty() && self.input.is_none() {
             return Err(crate::error::ReverDNSError::ConfigError(
                 "Either provide IPs as arguments or use --input flag".to_string(),
             ));
         }
 
+        if self.format == OutputFormat::Sqlite && self.output.is_none() {
+            return Err(crate::error::ReverDNSError::ConfigError(
+                "The sqlite format needs an --output database file".to_string(),
+            ));
+        }
+
         if self.concurrency == 0 {
             return Err(crate::error::ReverDNSError::ConfigError(
                 "Concurrency must be greater than 0".to_string(),
@@ -135,6 +536,24 @@ impl Args {
             ));
         }
 
+        if self.cache && self.cache_max_size == 0 {
+            return Err(crate::error::ReverDNSError::ConfigError(
+                "Cache size must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.rate_burst == Some(0) {
+            return Err(crate::error::ReverDNSError::ConfigError(
+                "Rate burst must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.eject_cooldown == 0 {
+            return Err(crate::error::ReverDNSError::ConfigError(
+                "Eject cooldown must be greater than 0".to_string(),
+            ));
+        }
+
         if self.timeout == 0 {
             return Err(crate::error::ReverDNSError::ConfigError(
                 "Timeout must be greater than 0".to_string(),
@@ -153,6 +572,11 @@ mod tests {
     fn test_output_format_display() {
         assert_eq!(OutputFormat::Json.to_string(), "json");
         assert_eq!(OutputFormat::Csv.to_string(), "csv");
+        assert_eq!(OutputFormat::Ndjson.to_string(), "ndjson");
+        assert_eq!(OutputFormat::Sqlite.to_string(), "sqlite");
+        assert_eq!(OutputFormat::Hosts.to_string(), "hosts");
+        assert_eq!(OutputFormat::Zone.to_string(), "zone");
+        assert_eq!(OutputFormat::Text.to_string(), "text");
     }
 
     #[test]
@@ -160,4 +584,32 @@ mod tests {
         assert_eq!(LogLevel::Debug.to_string(), "debug");
         assert_eq!(LogLevel::Info.to_string(), "info");
     }
+
+    #[test]
+    fn test_log_format_display() {
+        assert_eq!(LogFormat::Text.to_string(), "text");
+        assert_eq!(LogFormat::Json.to_string(), "json");
+    }
+
+    #[test]
+    fn test_cache_command() {
+        let args = Args::parse_from(["reverdns", "cache", "stats", "--cache-db", "ptr.db"]);
+        assert_eq!(
+            args.command,
+            Some(Command::Cache {
+                action: CacheAction::Stats
+            })
+        );
+        assert_eq!(args.cache_db, Some(PathBuf::from("ptr.db")));
+        assert!(args.validate().is_ok());
+
+        let args = Args::parse_from(["reverdns", "--max-age", "60", "cache", "export", "-o", "x"]);
+        assert_eq!(args.max_age(), Some(Duration::from_secs(60)));
+        assert!(args.validate().is_err());
+
+        // Addresses are still taken as positional arguments
+        let args = Args::parse_from(["reverdns", "--cache-db", "ptr.db", "192.0.2.1"]);
+        assert_eq!(args.command, None);
+        assert_eq!(args.ips, vec!["192.0.2.1"]);
+    }
 }
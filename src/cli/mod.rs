@@ -1,3 +1,3 @@
 pub mod args;
 
-pub use args::{Args, OutputFormat, LogLevel};
+pub use args::{Args, CacheAction, Command, OutputFormat, LogLevel, LogFormat};
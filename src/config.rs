@@ -0,0 +1,796 @@
+//! Configuration file support (`.reverdns.toml`)
+//!
+//! Settings are layered with the following precedence (highest first):
+//! command-line arguments, `REVERDNS_*` environment variables, the
+//! configuration file, built-in defaults.
+
+use crate::cli::{Args, LogFormat, LogLevel, OutputFormat};
+use crate::dns::{
+    DohMethod, Jitter, LookupStatus, Strategy, SystemResolvers, TransportMode, UpstreamSpec,
+};
+use crate::error::{Result, ReverDNSError};
+use crate::output::CsvHostnames;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, FromArgMatches};
+use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+use serde::de::{Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::net::IpAddr;
+use std::num::{NonZeroU32, NonZeroU64, NonZeroUsize};
+use std::path::{Path, PathBuf};
+
+/// File name looked up in the current directory
+pub const LOCAL_CONFIG_FILE: &str = ".reverdns.toml";
+
+/// Parsed `.reverdns.toml`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub dns: DnsConfig,
+    pub resolvers: ResolversConfig,
+    pub output: OutputConfig,
+    pub logging: LoggingConfig,
+    pub web: WebConfig,
+    pub performance: PerformanceConfig,
+    pub security: SecurityConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DnsConfig {
+    pub timeout: Option<NonZeroU64>,
+    pub concurrency: Option<NonZeroUsize>,
+    pub rate_limit: Option<NonZeroU32>,
+    pub rate_burst: Option<NonZeroU32>,
+    pub rate_limit_wait: Option<u64>,
+    pub strategy: Option<Strategy>,
+    pub transport: Option<TransportMode>,
+    pub verify_forward: Option<bool>,
+    pub eject_after: Option<u32>,
+    pub eject_cooldown: Option<NonZeroU64>,
+    pub retry_count: Option<u32>,
+    pub retry_backoff_ms: Option<u64>,
+    pub retry_max_backoff_ms: Option<u64>,
+    pub retry_jitter: Option<Jitter>,
+    pub retry_on: Option<Vec<LookupStatus>>,
+    pub retry_same_resolver: Option<bool>,
+}
+
+/// Either `[resolvers]` with `custom` (plain DNS) and `dot` lists of resolver
+/// strings and the `system` switch, or a `[[resolvers]]` table per plain DNS
+/// upstream
+#[derive(Debug)]
+pub enum ResolversConfig {
+    Custom(CustomResolvers),
+    Upstreams(Vec<UpstreamSpec>),
+}
+
+impl<'de> Deserialize<'de> for ResolversConfig {
+    /// Picks the variant from the shape of the value (table or array of
+    /// tables) rather than trying each in turn, so that an error names the
+    /// offending key instead of "data did not match any variant"
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct ResolversVisitor;
+
+        impl<'de> Visitor<'de> for ResolversVisitor {
+            type Value = ResolversConfig;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a [resolvers] table or [[resolvers]] tables")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> std::result::Result<Self::Value, A::Error> {
+                CustomResolvers::deserialize(MapAccessDeserializer::new(map))
+                    .map(ResolversConfig::Custom)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(
+                self,
+                seq: A,
+            ) -> std::result::Result<Self::Value, A::Error> {
+                Vec::deserialize(SeqAccessDeserializer::new(seq)).map(ResolversConfig::Upstreams)
+            }
+        }
+
+        deserializer.deserialize_any(ResolversVisitor)
+    }
+}
+
+impl Default for ResolversConfig {
+    fn default() -> Self {
+        Self::Custom(CustomResolvers::default())
+    }
+}
+
+impl ResolversConfig {
+    /// Resolvers in `--resolver` syntax, if any were configured
+    pub fn specs(&self) -> Option<Vec<String>> {
+        match self {
+            Self::Custom(custom) => custom.custom.clone(),
+            Self::Upstreams(upstreams) => Some(upstreams.iter().map(|u| u.to_string()).collect()),
+        }
+    }
+
+    /// DNS-over-TLS resolvers in `--dot-resolver` syntax, if any were
+    /// configured
+    pub fn dot_specs(&self) -> Option<Vec<String>> {
+        match self {
+            Self::Custom(custom) => custom.dot.clone(),
+            Self::Upstreams(_) => None,
+        }
+    }
+
+    /// Whether to use the system resolvers, if configured
+    pub fn system(&self) -> Option<bool> {
+        match self {
+            Self::Custom(custom) => custom.system,
+            Self::Upstreams(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CustomResolvers {
+    pub custom: Option<Vec<String>>,
+    pub dot: Option<Vec<String>>,
+    pub system: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    pub format: Option<OutputFormat>,
+    pub csv_hostnames: Option<CsvHostnames>,
+    pub ndjson_summary: Option<bool>,
+    pub include_metadata: Option<bool>,
+    pub pretty_print: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    pub level: Option<LogLevel>,
+    pub format: Option<LogFormat>,
+    pub file: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebConfig {
+    pub enabled: Option<bool>,
+    pub port: Option<u16>,
+    pub bind: Option<IpAddr>,
+    pub cors_enabled: Option<bool>,
+    pub cors_origins: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PerformanceConfig {
+    pub cache_enabled: Option<bool>,
+    pub cache_ttl: Option<u64>,
+    pub cache_max_size: Option<NonZeroUsize>,
+    pub cache_db: Option<PathBuf>,
+    pub cache_max_age: Option<u64>,
+    pub connection_pooling: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecurityConfig {
+    pub dns_over_https: Option<bool>,
+    pub doh_provider: Option<OneOrMany>,
+    pub doh_method: Option<DohMethod>,
+    pub ca_file: Option<PathBuf>,
+    pub validate_dnssec: Option<bool>,
+    pub reject_bogus: Option<bool>,
+    pub rate_limit_per_ip: Option<bool>,
+}
+
+/// A single string or a list of them
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    pub fn to_vec(&self) -> Vec<String> {
+        match self {
+            Self::One(one) => vec![one.clone()],
+            Self::Many(many) => many.clone(),
+        }
+    }
+}
+
+/// A configuration file together with the path it was read from
+#[derive(Debug)]
+pub struct LoadedConfig {
+    pub path: PathBuf,
+    pub config: Config,
+}
+
+impl Config {
+    /// Parse configuration from TOML text; `path` is only used in error messages
+    pub fn parse(content: &str, path: &Path) -> Result<Self> {
+        toml::from_str(content).map_err(|e| {
+            let location = match e.span() {
+                Some(span) => {
+                    let line = content[..span.start].matches('\n').count() + 1;
+                    format!("{}:{}", path.display(), line)
+                }
+                None => path.display().to_string(),
+            };
+            ReverDNSError::ConfigError(format!("{}: {}", location, e.message()))
+        })
+    }
+
+    /// Read and parse a configuration file
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Err(ReverDNSError::FileNotFound(path.display().to_string()));
+        }
+        let content = fs::read_to_string(path)?;
+        Self::parse(&content, path)
+    }
+
+    /// Locate and load the configuration file.
+    ///
+    /// An explicit path must exist. Otherwise `./.reverdns.toml` and then
+    /// `$XDG_CONFIG_HOME/reverdns/config.toml` (falling back to
+    /// `~/.config/reverdns/config.toml`) are tried.
+    pub fn discover(explicit: Option<&Path>) -> Result<Option<LoadedConfig>> {
+        let path = match explicit {
+            Some(path) => path.to_path_buf(),
+            None => match default_search_paths().into_iter().find(|p| p.is_file()) {
+                Some(path) => path,
+                None => return Ok(None),
+            },
+        };
+
+        let config = Self::load(&path)?;
+        Ok(Some(LoadedConfig { path, config }))
+    }
+
+    /// Fill every argument not given on the command line or through the
+    /// environment with the value from this configuration
+    pub fn apply_to(&self, args: &mut Args, matches: &ArgMatches) {
+        let unset = |id: &str| {
+            !matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine) | Some(ValueSource::EnvVariable)
+            )
+        };
+
+        if let (true, Some(v)) = (unset("timeout"), self.dns.timeout) {
+            args.timeout = v.get();
+        }
+        if let (true, Some(v)) = (unset("concurrency"), self.dns.concurrency) {
+            args.concurrency = v.get();
+        }
+        if let (true, Some(v)) = (unset("rate_limit"), self.dns.rate_limit) {
+            args.rate_limit = v.get();
+        }
+        if let (true, Some(v)) = (unset("rate_burst"), self.dns.rate_burst) {
+            args.rate_burst = Some(v.get());
+        }
+        if let (true, Some(v)) = (unset("rate_limit_wait"), self.dns.rate_limit_wait) {
+            args.rate_limit_wait = Some(v);
+        }
+        if let (true, Some(v)) = (unset("strategy"), self.dns.strategy) {
+            args.strategy = v;
+        }
+        if let (true, Some(v)) = (unset("transport"), self.dns.transport) {
+            args.transport = v;
+        }
+        if let (true, Some(v)) = (unset("verify_forward"), self.dns.verify_forward) {
+            args.verify_forward = v;
+        }
+        if let (true, Some(v)) = (unset("eject_after"), self.dns.eject_after) {
+            args.eject_after = v;
+        }
+        if let (true, Some(v)) = (unset("eject_cooldown"), self.dns.eject_cooldown) {
+            args.eject_cooldown = v.get();
+        }
+        if let (true, Some(v)) = (unset("retry_count"), self.dns.retry_count) {
+            args.retry_count = v;
+        }
+        if let (true, Some(v)) = (unset("retry_backoff"), self.dns.retry_backoff_ms) {
+            args.retry_backoff = v;
+        }
+        if let (true, Some(v)) = (unset("retry_max_backoff"), self.dns.retry_max_backoff_ms) {
+            args.retry_max_backoff = v;
+        }
+        if let (true, Some(v)) = (unset("retry_jitter"), self.dns.retry_jitter) {
+            args.retry_jitter = v;
+        }
+        if let (true, Some(v)) = (unset("retry_on"), &self.dns.retry_on) {
+            args.retry_on = v.clone();
+        }
+        if let (true, Some(v)) = (unset("retry_same_resolver"), self.dns.retry_same_resolver) {
+            args.retry_same_resolver = v;
+        }
+        if let (true, Some(v)) = (unset("resolver"), self.resolvers.specs()) {
+            args.resolver = v;
+        }
+        if let (true, Some(v)) = (unset("dot_resolver"), self.resolvers.dot_specs()) {
+            args.dot_resolver = v;
+        }
+        // The system resolvers stand in for the built-in defaults, so they
+        // are not added to resolvers given on the command line
+        let defaults = unset("resolver") && unset("dot_resolver") && unset("dns_over_https");
+        if let (true, Some(v)) = (
+            unset("system_resolvers") && defaults,
+            self.resolvers.system(),
+        ) {
+            args.system_resolvers = v;
+        }
+        if let (true, Some(v)) = (unset("format"), self.output.format) {
+            args.format = v;
+        }
+        if let (true, Some(v)) = (unset("csv_hostnames"), self.output.csv_hostnames) {
+            args.csv_hostnames = v;
+        }
+        if let (true, Some(v)) = (unset("ndjson_summary"), self.output.ndjson_summary) {
+            args.ndjson_summary = v;
+        }
+        if let (true, Some(v)) = (unset("log_level"), self.logging.level) {
+            args.log_level = v;
+        }
+        if let (true, Some(v)) = (unset("log_format"), self.logging.format) {
+            args.log_format = v;
+        }
+        if let (true, Some(v)) = (unset("web_server"), self.web.enabled) {
+            args.web_server = v;
+        }
+        if let (true, Some(v)) = (unset("web_port"), self.web.port) {
+            args.web_port = v;
+        }
+        if let (true, Some(v)) = (unset("web_bind"), self.web.bind) {
+            args.web_bind = v;
+        }
+        if let (true, Some(v)) = (unset("cache"), self.performance.cache_enabled) {
+            args.cache = v;
+        }
+        if let (true, Some(v)) = (unset("cache_ttl"), self.performance.cache_ttl) {
+            args.cache_ttl = v;
+        }
+        if let (true, Some(v)) = (unset("cache_max_size"), self.performance.cache_max_size) {
+            args.cache_max_size = v.get();
+        }
+        if let (true, Some(v)) = (unset("cache_db"), &self.performance.cache_db) {
+            args.cache_db = Some(v.clone());
+        }
+        if let (true, Some(v)) = (unset("max_age"), self.performance.cache_max_age) {
+            args.max_age = Some(v);
+        }
+        if let (true, Some(v)) = (unset("dns_over_https"), self.security.dns_over_https) {
+            args.dns_over_https = v;
+        }
+        if let (true, Some(v)) = (unset("doh_provider"), &self.security.doh_provider) {
+            args.doh_provider = v.to_vec();
+        }
+        if let (true, Some(v)) = (unset("doh_method"), self.security.doh_method) {
+            args.doh_method = v;
+        }
+        if let (true, Some(v)) = (unset("ca_file"), &self.security.ca_file) {
+            args.ca_file = Some(v.clone());
+        }
+        if let (true, Some(v)) = (unset("validate_dnssec"), self.security.validate_dnssec) {
+            args.validate_dnssec = v;
+        }
+        if let (true, Some(v)) = (unset("reject_bogus"), self.security.reject_bogus) {
+            args.reject_bogus = v;
+        }
+    }
+
+    /// Keys that request behaviour this build does not implement
+    pub fn unsupported_keys(&self) -> Vec<&'static str> {
+        let mut keys = Vec::new();
+        if self.output.include_metadata == Some(false) {
+            keys.push("output.include_metadata");
+        }
+        if self.output.pretty_print == Some(false) {
+            keys.push("output.pretty_print");
+        }
+        if self.logging.file.is_some() {
+            keys.push("logging.file");
+        }
+        if self.web.cors_enabled == Some(true) || self.web.cors_origins.is_some() {
+            keys.push("web.cors_enabled/cors_origins");
+        }
+        if self.performance.connection_pooling.is_some() {
+            keys.push("performance.connection_pooling");
+        }
+        if self.security.rate_limit_per_ip == Some(true) {
+            keys.push("security.rate_limit_per_ip");
+        }
+        keys
+    }
+}
+
+/// Candidate configuration files, in lookup order
+pub fn default_search_paths() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from(LOCAL_CONFIG_FILE)];
+
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    if let Some(dir) = config_home {
+        paths.push(dir.join("reverdns").join("config.toml"));
+    }
+
+    paths
+}
+
+/// Build [`Args`] from parsed command-line matches, layering the
+/// configuration file underneath them
+pub fn resolve_args(matches: &ArgMatches) -> Result<(Args, Option<LoadedConfig>)> {
+    let mut args =
+        Args::from_arg_matches(matches).map_err(|e| ReverDNSError::ConfigError(e.to_string()))?;
+
+    if args.no_config {
+        return Ok((args, None));
+    }
+
+    let loaded = Config::discover(args.config.as_deref())?;
+    if let Some(loaded) = &loaded {
+        loaded.config.apply_to(&mut args, matches);
+    }
+
+    Ok((args, loaded))
+}
+
+/// Take the lookup timeout and retry count from the `timeout:N` and
+/// `attempts:N` options of `resolv.conf` (as N - 1 retries) where neither the
+/// command line, the environment nor the configuration file sets them, and
+/// keep `system` for the upstreams
+pub fn apply_system_resolvers(
+    args: &mut Args,
+    matches: &ArgMatches,
+    config: Option<&Config>,
+    system: SystemResolvers,
+) {
+    let unset = |id: &str| {
+        matches!(
+            matches.value_source(id),
+            None | Some(ValueSource::DefaultValue)
+        )
+    };
+
+    if let (true, None, Some(v)) = (
+        unset("timeout"),
+        config.and_then(|c| c.dns.timeout),
+        system.timeout_secs(),
+    ) {
+        args.timeout = v;
+    }
+    if let (true, None, Some(v)) = (
+        unset("retry_count"),
+        config.and_then(|c| c.dns.retry_count),
+        system.retries(),
+    ) {
+        args.retry_count = v;
+    }
+    args.system_conf = Some(system);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn matches(argv: &[&str]) -> ArgMatches {
+        Args::command().try_get_matches_from(argv).unwrap()
+    }
+
+    #[test]
+    fn test_parse_example_config() {
+        let config = Config::parse(
+            include_str!("../.reverdns.toml.example"),
+            Path::new(".reverdns.toml.example"),
+        )
+        .unwrap();
+        assert_eq!(config.dns.timeout.map(|v| v.get()), Some(5));
+        assert_eq!(config.resolvers.specs().unwrap().len(), 4);
+        assert_eq!(config.output.format, Some(OutputFormat::Json));
+    }
+
+    #[test]
+    fn test_unknown_key_reports_line() {
+        let content = "[dns]\ntimeout = 5\ntimeuot = 10\n";
+        let err = Config::parse(content, Path::new("test.toml")).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("test.toml:3"), "{}", msg);
+        assert!(msg.contains("timeuot"), "{}", msg);
+    }
+
+    #[test]
+    fn test_resolvers_typo_reports_key() {
+        let content = "[resolvers]\ncustom = [\"8.8.8.8\"]\ncustm = [\"1.1.1.1\"]\n";
+        let err = Config::parse(content, Path::new("test.toml")).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("test.toml:3"), "{}", msg);
+        assert!(msg.contains("custm"), "{}", msg);
+    }
+
+    #[test]
+    fn test_bad_value_reports_line() {
+        let content = "[dns]\n\nconcurrency = 0\n";
+        let err = Config::parse(content, Path::new("test.toml")).unwrap_err();
+        assert!(err.to_string().contains("test.toml:3"), "{}", err);
+
+        let content = "[output]\nformat = \"xml\"\n";
+        let err = Config::parse(content, Path::new("test.toml")).unwrap_err();
+        assert!(err.to_string().contains("test.toml:2"), "{}", err);
+    }
+
+    #[test]
+    fn test_cli_takes_precedence() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("reverdns.toml");
+        fs::write(
+            &path,
+            "[dns]\ntimeout = 7\nconcurrency = 20\n\n[resolvers]\ncustom = [\"9.9.9.9\"]\n",
+        )
+        .unwrap();
+
+        let path_str = path.to_str().unwrap();
+        let m = matches(&[
+            "reverdns",
+            "--config",
+            path_str,
+            "--timeout",
+            "2",
+            "8.8.8.8",
+        ]);
+        let (args, loaded) = resolve_args(&m).unwrap();
+
+        assert_eq!(loaded.unwrap().path, path);
+        assert_eq!(args.timeout, 2);
+        assert_eq!(args.concurrency, 20);
+        assert_eq!(args.resolver, vec!["9.9.9.9".to_string()]);
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("reverdns.toml");
+        fs::write(
+            &path,
+            "[dns]\nretry_count = 2\nretry_max_backoff_ms = 800\nretry_jitter = \"decorrelated\"\n\
+             retry_on = [\"servfail\"]\nretry_same_resolver = true\n",
+        )
+        .unwrap();
+
+        let path_str = path.to_str().unwrap();
+        let m = matches(&["reverdns", "--config", path_str, "8.8.8.8"]);
+        let policy = resolve_args(&m).unwrap().0.retry_policy();
+        assert_eq!(policy.max_retries, 2);
+        assert_eq!(policy.max_backoff, Duration::from_millis(800));
+        assert_eq!(policy.jitter, Jitter::Decorrelated);
+        assert_eq!(policy.retry_on, vec![LookupStatus::ServFail]);
+        assert!(!policy.switch_resolver);
+
+        let m = matches(&[
+            "reverdns",
+            "--config",
+            path_str,
+            "--retry-on",
+            "timeout,nxdomain,network_error",
+            "8.8.8.8",
+        ]);
+        let policy = resolve_args(&m).unwrap().0.retry_policy();
+        assert_eq!(
+            policy.retry_on,
+            vec![
+                LookupStatus::Timeout,
+                LookupStatus::NxDomain,
+                LookupStatus::NetworkError
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cache_db() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("reverdns.toml");
+        fs::write(
+            &path,
+            "[performance]\ncache_db = \"ptr.db\"\ncache_max_age = 86400\n",
+        )
+        .unwrap();
+
+        let path_str = path.to_str().unwrap();
+        let m = matches(&["reverdns", "--config", path_str, "cache", "prune"]);
+        let args = resolve_args(&m).unwrap().0;
+        assert_eq!(args.cache_db, Some(PathBuf::from("ptr.db")));
+        assert_eq!(args.max_age(), Some(Duration::from_secs(86400)));
+
+        // Given after the subcommand, the flags still override the file
+        let m = matches(&[
+            "reverdns",
+            "--config",
+            path_str,
+            "cache",
+            "prune",
+            "--cache-db",
+            "other.db",
+            "--max-age",
+            "60",
+        ]);
+        let args = resolve_args(&m).unwrap().0;
+        assert_eq!(args.cache_db, Some(PathBuf::from("other.db")));
+        assert_eq!(args.max_age, Some(60));
+    }
+
+    #[test]
+    fn test_missing_explicit_config() {
+        let m = matches(&[
+            "reverdns",
+            "--config",
+            "/nonexistent/reverdns.toml",
+            "8.8.8.8",
+        ]);
+        assert!(matches!(
+            resolve_args(&m),
+            Err(ReverDNSError::FileNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_no_config_skips_discovery() {
+        let m = matches(&["reverdns", "--no-config", "8.8.8.8"]);
+        let (_, loaded) = resolve_args(&m).unwrap();
+        assert!(loaded.is_none());
+    }
+
+    #[test]
+    fn test_resolver_tables() {
+        let content = "[[resolvers]]\naddress = \"8.8.8.8\"\nrate_limit = 50\n\n\
+                       [[resolvers]]\naddress = \"10.0.0.53\"\nmax_in_flight = 200\nweight = 4\n";
+        let config = Config::parse(content, Path::new("test.toml")).unwrap();
+        assert_eq!(
+            config.resolvers.specs().unwrap(),
+            vec!["8.8.8.8@50qps", "10.0.0.53@max=200/weight=4"]
+        );
+
+        let content = "[[resolvers]]\naddress = \"8.8.8.8\"\nqps = 50\n";
+        let err = Config::parse(content, Path::new("test.toml")).unwrap_err();
+        assert!(err.to_string().contains("test.toml:3"), "{}", err);
+        assert!(err.to_string().contains("qps"), "{}", err);
+
+        let content = "[dns]\ntransport = \"udp-then-tcp\"\nverify_forward = true\n\n\
+                       [[resolvers]]\naddress = \"10.0.0.53\"\ntransport = \"tcp\"\n";
+        let config = Config::parse(content, Path::new("test.toml")).unwrap();
+        assert_eq!(config.dns.transport, Some(TransportMode::UdpThenTcp));
+        assert_eq!(config.dns.verify_forward, Some(true));
+        assert_eq!(
+            config.resolvers.specs().unwrap(),
+            vec!["10.0.0.53@transport=tcp"]
+        );
+
+        let content = "[[resolvers]]\naddress = \"127.0.0.1:5353\"\n\n\
+                       [[resolvers]]\naddress = \"[2001:db8::1]:5300\"\n";
+        let config = Config::parse(content, Path::new("test.toml")).unwrap();
+        assert_eq!(
+            config.resolvers.specs().unwrap(),
+            vec!["127.0.0.1:5353", "[2001:db8::1]:5300"]
+        );
+
+        let content = "[[resolvers]]\naddress = \"dns.example\"\n";
+        assert!(Config::parse(content, Path::new("test.toml")).is_err());
+    }
+
+    #[test]
+    fn test_system_resolvers_default() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("reverdns.toml");
+        fs::write(&path, "[resolvers]\nsystem = true\n").unwrap();
+        let config = path.to_str().unwrap();
+
+        let (args, _) =
+            resolve_args(&matches(&["reverdns", "--config", config, "8.8.8.8"])).unwrap();
+        assert!(args.system_resolvers);
+
+        // Resolvers on the command line replace the configured default
+        let m = matches(&["reverdns", "--config", config, "-r", "9.9.9.9", "8.8.8.8"]);
+        let (args, _) = resolve_args(&m).unwrap();
+        assert!(!args.system_resolvers);
+        assert_eq!(args.resolver, vec!["9.9.9.9"]);
+    }
+
+    #[test]
+    fn test_system_resolver_options() {
+        let system = || {
+            SystemResolvers::parse("nameserver 10.0.0.2\noptions timeout:2 attempts:4\n")
+                .unwrap()
+        };
+
+        let m = matches(&["reverdns", "--system-resolvers", "8.8.8.8"]);
+        let (mut args, _) = resolve_args(&m).unwrap();
+        apply_system_resolvers(&mut args, &m, None, system());
+        assert_eq!(args.timeout, 2);
+        assert_eq!(args.retry_count, 3);
+        assert!(args.system_conf.is_some());
+
+        // The command line and the configuration file win over resolv.conf
+        let config = Config::parse("[dns]\nretry_count = 1\n", Path::new("test.toml")).unwrap();
+        let m = matches(&["reverdns", "--timeout", "9", "8.8.8.8"]);
+        let (mut args, _) = resolve_args(&m).unwrap();
+        config.apply_to(&mut args, &m);
+        apply_system_resolvers(&mut args, &m, Some(&config), system());
+        assert_eq!(args.timeout, 9);
+        assert_eq!(args.retry_count, 1);
+
+        // Options missing from resolv.conf leave the defaults alone
+        let m = matches(&["reverdns", "8.8.8.8"]);
+        let (mut args, _) = resolve_args(&m).unwrap();
+        let plain = SystemResolvers::parse("nameserver 10.0.0.2\n").unwrap();
+        apply_system_resolvers(&mut args, &m, None, plain);
+        assert_eq!(args.timeout, 5);
+        assert_eq!(args.retry_count, 3);
+    }
+
+    #[test]
+    fn test_encrypted_resolvers() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("reverdns.toml");
+        fs::write(
+            &path,
+            "[resolvers]\ndot = [\"1.1.1.1#cloudflare-dns.com\"]\n\n\
+             [security]\nca_file = \"ca.pem\"\ndoh_method = \"get\"\n\
+             doh_provider = [\"https://a.example/dns-query\", \"https://b.example/q\"]\n",
+        )
+        .unwrap();
+
+        let m = matches(&["reverdns", "--config", path.to_str().unwrap(), "8.8.8.8"]);
+        let (args, _) = resolve_args(&m).unwrap();
+
+        assert!(args.resolver.is_empty());
+        assert_eq!(args.dot_resolver, vec!["1.1.1.1#cloudflare-dns.com"]);
+        assert_eq!(args.ca_file, Some(PathBuf::from("ca.pem")));
+        assert_eq!(args.doh_provider.len(), 2);
+        assert_eq!(args.doh_method, DohMethod::Get);
+
+        let content = "[security]\ndoh_provider = \"https://dns.google/dns-query\"\n";
+        let config = Config::parse(content, Path::new("test.toml")).unwrap();
+        assert_eq!(config.security.doh_provider.unwrap().to_vec().len(), 1);
+    }
+
+    #[test]
+    fn test_unsupported_keys() {
+        let config = Config::parse(
+            "[performance]\nconnection_pooling = true\n\n\
+             [security]\nvalidate_dnssec = true\nrate_limit_per_ip = true\n",
+            Path::new("test.toml"),
+        )
+        .unwrap();
+        assert_eq!(
+            config.unsupported_keys(),
+            vec![
+                "performance.connection_pooling",
+                "security.rate_limit_per_ip"
+            ]
+        );
+
+        // The shipped example only sets keys that take effect
+        let example = Config::parse(
+            include_str!("../.reverdns.toml.example"),
+            Path::new(".reverdns.toml.example"),
+        )
+        .unwrap();
+        assert!(example.unsupported_keys().is_empty());
+    }
+}
@@ -0,0 +1,248 @@
+use super::resolver::{LookupResult, LookupStatus};
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Whether a result came from the cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheStatus {
+    Hit,
+    Miss,
+}
+
+impl std::fmt::Display for CacheStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CacheStatus::Hit => write!(f, "hit"),
+            CacheStatus::Miss => write!(f, "miss"),
+        }
+    }
+}
+
+/// Bounded LRU cache of lookup results, keyed by address.
+///
+/// Answers are kept for their PTR TTL, NXDOMAIN and NODATA results for the
+/// negative TTL from the zone's SOA record, neither longer than `max_ttl`.
+/// Other failures are never cached.
+pub struct LookupCache {
+    entries: Mutex<LruCache<IpAddr, Entry>>,
+    max_ttl: Duration,
+}
+
+struct Entry {
+    result: LookupResult,
+    expires: Instant,
+}
+
+impl LookupCache {
+    pub fn new(capacity: NonZeroUsize, max_ttl: Duration) -> Self {
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+            max_ttl,
+        }
+    }
+
+    /// Unexpired result for `ip`, marked as a hit, with its TTL counting
+    /// down from when it was stored
+    pub fn get(&self, ip: IpAddr) -> Option<LookupResult> {
+        self.get_at(ip, Instant::now())
+    }
+
+    /// Store `result` for `ip`; `negative_ttl` is the SOA-derived TTL of an
+    /// NXDOMAIN or NODATA response
+    pub fn insert(&self, ip: IpAddr, result: &LookupResult, negative_ttl: Option<u32>) {
+        self.insert_at(ip, result, negative_ttl, Instant::now())
+    }
+
+    /// Number of cached results, including expired ones not yet evicted
+    pub fn len(&self) -> usize {
+        self.entries().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn get_at(&self, ip: IpAddr, now: Instant) -> Option<LookupResult> {
+        let mut entries = self.entries();
+        let entry = entries.get(&ip)?;
+        if entry.expires <= now {
+            entries.pop(&ip);
+            return None;
+        }
+
+        let remaining = entry.expires.duration_since(now).as_secs();
+        let mut result = entry.result.clone();
+        result.ttl = result
+            .ttl
+            .map(|_| u32::try_from(remaining).unwrap_or(u32::MAX));
+        result.cache = Some(CacheStatus::Hit);
+        result.latency_ms = 0;
+        result.attempts = 0;
+        Some(result)
+    }
+
+    fn insert_at(
+        &self,
+        ip: IpAddr,
+        result: &LookupResult,
+        negative_ttl: Option<u32>,
+        now: Instant,
+    ) {
+        let Some(ttl) = cache_ttl(result, negative_ttl) else {
+            return;
+        };
+        let ttl = Duration::from_secs(ttl.into()).min(self.max_ttl);
+        if ttl.is_zero() {
+            return;
+        }
+
+        let mut result = result.clone();
+        result.cache = None;
+        self.entries().put(
+            ip,
+            Entry {
+                result,
+                expires: now + ttl,
+            },
+        );
+    }
+
+    fn entries(&self) -> std::sync::MutexGuard<'_, LruCache<IpAddr, Entry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// How long `result` may be cached: the PTR TTL of an answer, the negative
+/// TTL of an NXDOMAIN or NODATA result, and never for other failures
+pub(crate) fn cache_ttl(result: &LookupResult, negative_ttl: Option<u32>) -> Option<u32> {
+    let ttl = match result.status {
+        LookupStatus::Success => result.ttl,
+        LookupStatus::NxDomain | LookupStatus::NoData => negative_ttl,
+        _ => None,
+    };
+    ttl.filter(|&ttl| ttl > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(ip: &str, status: LookupStatus, ttl: Option<u32>) -> LookupResult {
+        LookupResult {
+            cache: Some(CacheStatus::Miss),
+            ttl,
+            latency_ms: 20,
+            attempts: 2,
+            ..LookupResult::new(ip, status, "192.0.2.53:53/udp")
+        }
+    }
+
+    fn cache(capacity: usize) -> LookupCache {
+        LookupCache::new(
+            NonZeroUsize::new(capacity).unwrap(),
+            Duration::from_secs(600),
+        )
+    }
+
+    #[test]
+    fn test_hit_counts_ttl_down() {
+        let cache = cache(10);
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+        let now = Instant::now();
+        cache.insert_at(
+            ip,
+            &result("192.0.2.1", LookupStatus::Success, Some(300)),
+            None,
+            now,
+        );
+
+        let hit = cache.get_at(ip, now + Duration::from_secs(100)).unwrap();
+        assert_eq!(hit.cache, Some(CacheStatus::Hit));
+        assert_eq!(hit.ttl, Some(200));
+        assert_eq!((hit.latency_ms, hit.attempts), (0, 0));
+        assert!(cache.get_at(ip, now + Duration::from_secs(300)).is_none());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_ttl_limits() {
+        let cache = cache(10);
+        let now = Instant::now();
+        let long: IpAddr = "192.0.2.1".parse().unwrap();
+        cache.insert_at(
+            long,
+            &result("192.0.2.1", LookupStatus::Success, Some(86400)),
+            None,
+            now,
+        );
+        assert!(cache.get_at(long, now + Duration::from_secs(599)).is_some());
+        assert!(cache.get_at(long, now + Duration::from_secs(600)).is_none());
+
+        // Zero TTLs and failures other than NXDOMAIN/NODATA are not kept
+        for (i, (status, ttl)) in [
+            (LookupStatus::Success, Some(0)),
+            (LookupStatus::ServFail, Some(300)),
+            (LookupStatus::Timeout, None),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let ip = IpAddr::from([192, 0, 2, 10 + i as u8]);
+            cache.insert_at(ip, &result(&ip.to_string(), status, ttl), Some(300), now);
+        }
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn test_negative_answers() {
+        let cache = cache(10);
+        let now = Instant::now();
+        let nxdomain: IpAddr = "192.0.2.1".parse().unwrap();
+        let without_soa: IpAddr = "192.0.2.2".parse().unwrap();
+        cache.insert_at(
+            nxdomain,
+            &result("192.0.2.1", LookupStatus::NxDomain, None),
+            Some(60),
+            now,
+        );
+        cache.insert_at(
+            without_soa,
+            &result("192.0.2.2", LookupStatus::NoData, None),
+            None,
+            now,
+        );
+
+        let hit = cache
+            .get_at(nxdomain, now + Duration::from_secs(59))
+            .unwrap();
+        assert_eq!(hit.status, LookupStatus::NxDomain);
+        assert_eq!(hit.ttl, None);
+        assert!(cache
+            .get_at(nxdomain, now + Duration::from_secs(60))
+            .is_none());
+        assert!(cache.get_at(without_soa, now).is_none());
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let cache = cache(2);
+        let now = Instant::now();
+        let ips: Vec<IpAddr> = (1..=3).map(|i| IpAddr::from([192, 0, 2, i])).collect();
+        let answer = |ip: &IpAddr| result(&ip.to_string(), LookupStatus::Success, Some(300));
+
+        cache.insert_at(ips[0], &answer(&ips[0]), None, now);
+        cache.insert_at(ips[1], &answer(&ips[1]), None, now);
+        assert!(cache.get_at(ips[0], now).is_some());
+        cache.insert_at(ips[2], &answer(&ips[2]), None, now);
+
+        assert!(cache.get_at(ips[0], now).is_some());
+        assert!(cache.get_at(ips[1], now).is_none());
+        assert!(cache.get_at(ips[2], now).is_some());
+    }
+}
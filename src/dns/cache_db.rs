@@ -0,0 +1,567 @@
+use super::cache::{cache_ttl, CacheStatus};
+use super::resolver::LookupResult;
+use crate::error::{Result, ReverDNSError};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tracing::warn;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS ptr_cache (
+        ip TEXT PRIMARY KEY NOT NULL,
+        status TEXT NOT NULL,
+        ttl INTEGER NOT NULL,
+        resolved_at INTEGER NOT NULL,
+        result TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS ptr_cache_resolved_at ON ptr_cache (resolved_at);
+";
+
+/// Condition, in SQL, for a row that is still reused at time `?1` (Unix
+/// seconds) with maximum age `?2` (NULL to go by TTL)
+const FRESH: &str =
+    "CASE WHEN ?2 IS NULL THEN ?1 - resolved_at < ttl ELSE ?1 - resolved_at <= ?2 END";
+
+/// Rows committed in one transaction, at most
+const BATCH_SIZE: usize = 500;
+
+/// Rows waiting for the writer, at most
+const QUEUE_SIZE: usize = 4 * BATCH_SIZE;
+
+/// Lookup results kept in an SQLite file and shared across runs.
+///
+/// Each row holds a result together with when it was resolved and its TTL
+/// (the PTR TTL, or the negative TTL for NXDOMAIN and NODATA). A row is
+/// reused while its TTL lasts or, with `max_age` set, while it is younger
+/// than that whatever its TTL.
+///
+/// Inserts are queued for a writer thread with its own connection, which
+/// commits whatever has queued up, up to [`BATCH_SIZE`] rows, in one
+/// transaction. Rows become visible once committed; [`CacheDb::flush`]
+/// waits for that, and dropping the database commits the rest. Lookups never
+/// wait for the writer: while [`QUEUE_SIZE`] rows are queued, further rows
+/// are dropped and counted instead.
+pub struct CacheDb {
+    conn: Mutex<Connection>,
+    max_age: Option<Duration>,
+    writer: Option<Writer>,
+    dropped: AtomicU64,
+}
+
+struct Writer {
+    queue: SyncSender<Queued>,
+    thread: JoinHandle<()>,
+}
+
+enum Queued {
+    Row(Row),
+    /// Acknowledged once every row queued before it is committed
+    Flush(mpsc::Sender<()>),
+}
+
+struct Row {
+    ip: String,
+    status: String,
+    ttl: u32,
+    resolved_at: i64,
+    result: String,
+}
+
+/// Contents of a cache database
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CacheDbStats {
+    pub entries: u64,
+    /// Rows that would be reused now
+    pub fresh: u64,
+    /// Rows per lookup status, most common first
+    pub statuses: Vec<(String, u64)>,
+    pub oldest: Option<DateTime<Utc>>,
+    pub newest: Option<DateTime<Utc>>,
+}
+
+/// A stored result, as written by [`CacheDb::export`]
+#[derive(Debug, Clone, Serialize)]
+struct ExportedEntry {
+    resolved_at: DateTime<Utc>,
+    ttl: u32,
+    result: LookupResult,
+}
+
+impl CacheDb {
+    /// Open or create the cache database at `path`
+    pub fn open(path: &Path, max_age: Option<Duration>) -> Result<Self> {
+        let conn = Connection::open(path).map_err(|e| db_error(path, e))?;
+        // Reads go on while the writer commits; WAL without a sync per
+        // commit keeps that cheap, at the risk of losing the last rows on a
+        // power failure
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .and_then(|_| conn.pragma_update(None, "synchronous", "NORMAL"))
+            .and_then(|_| conn.execute_batch(SCHEMA))
+            .map_err(|e| db_error(path, e))?;
+        let writer = Connection::open(path)
+            .and_then(|writer| {
+                writer.pragma_update(None, "synchronous", "NORMAL")?;
+                Ok(writer)
+            })
+            .map_err(|e| db_error(path, e))?;
+
+        let (queue, rows) = mpsc::sync_channel(QUEUE_SIZE);
+        let thread = std::thread::Builder::new()
+            .name("cache-db-writer".to_string())
+            .spawn(move || write_rows(writer, rows))?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            max_age,
+            writer: Some(Writer { queue, thread }),
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    /// Stored result for `ip` if it is still reused, marked as a hit, with
+    /// its TTL counting down from when it was resolved, and the seconds
+    /// left of that TTL
+    pub fn get(&self, ip: IpAddr) -> Result<Option<(LookupResult, u32)>> {
+        self.get_at(ip, Utc::now().timestamp())
+    }
+
+    /// Queue `result` for `ip` to be stored if it may be cached, or drop it
+    /// if the queue is full; `negative_ttl` is the SOA-derived TTL of an
+    /// NXDOMAIN or NODATA response
+    pub fn insert(
+        &self,
+        ip: IpAddr,
+        result: &LookupResult,
+        negative_ttl: Option<u32>,
+    ) -> Result<()> {
+        self.insert_at(ip, result, negative_ttl, Utc::now().timestamp())
+    }
+
+    /// Wait until every queued row is committed
+    pub fn flush(&self) -> Result<()> {
+        let (done, committed) = mpsc::channel();
+        self.writer
+            .as_ref()
+            .and_then(|writer| writer.queue.send(Queued::Flush(done)).ok())
+            .ok_or_else(writer_stopped)?;
+        committed.recv().map_err(|_| writer_stopped())
+    }
+
+    /// Rows dropped because the writer fell behind
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Delete every row that is no longer reused, returning how many
+    pub fn prune(&self) -> Result<usize> {
+        self.prune_at(Utc::now().timestamp())
+    }
+
+    /// Count the stored rows
+    pub fn stats(&self) -> Result<CacheDbStats> {
+        self.stats_at(Utc::now().timestamp())
+    }
+
+    /// Write every stored row as a line of JSON, oldest first, returning how
+    /// many were written
+    pub fn export<W: Write>(&self, mut out: W) -> Result<usize> {
+        let conn = self.conn();
+        let mut statement = conn
+            .prepare("SELECT resolved_at, ttl, result FROM ptr_cache ORDER BY resolved_at, ip")
+            .map_err(query_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, u32>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })
+            .map_err(query_error)?;
+
+        let mut written = 0;
+        for row in rows {
+            let (resolved_at, ttl, result) = row.map_err(query_error)?;
+            let entry = ExportedEntry {
+                resolved_at: timestamp(resolved_at),
+                ttl,
+                result: serde_json::from_str(&result)?,
+            };
+            serde_json::to_writer(&mut out, &entry)?;
+            out.write_all(b"\n")?;
+            written += 1;
+        }
+        out.flush()?;
+        Ok(written)
+    }
+
+    fn get_at(&self, ip: IpAddr, now: i64) -> Result<Option<(LookupResult, u32)>> {
+        let row = self
+            .conn()
+            .query_row(
+                &format!(
+                    "SELECT resolved_at, ttl, result FROM ptr_cache WHERE ip = ?3 AND {}",
+                    FRESH
+                ),
+                params![now, self.max_age_secs(), ip.to_string()],
+                |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(query_error)?;
+        let Some((resolved_at, ttl, result)) = row else {
+            return Ok(None);
+        };
+
+        let mut result: LookupResult = serde_json::from_str(&result)?;
+        let remaining = u32::try_from((ttl - (now - resolved_at)).max(0)).unwrap_or(u32::MAX);
+        result.ttl = result.ttl.map(|_| remaining);
+        result.cache = Some(CacheStatus::Hit);
+        result.latency_ms = 0;
+        result.attempts = 0;
+        Ok(Some((result, remaining)))
+    }
+
+    fn insert_at(
+        &self,
+        ip: IpAddr,
+        result: &LookupResult,
+        negative_ttl: Option<u32>,
+        now: i64,
+    ) -> Result<()> {
+        let Some(ttl) = cache_ttl(result, negative_ttl) else {
+            return Ok(());
+        };
+        let mut result = result.clone();
+        result.cache = None;
+        let row = Queued::Row(Row {
+            ip: ip.to_string(),
+            status: result.status.to_string(),
+            ttl,
+            resolved_at: now,
+            result: serde_json::to_string(&result)?,
+        });
+        let writer = self.writer.as_ref().ok_or_else(writer_stopped)?;
+        match writer.queue.try_send(row) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(TrySendError::Disconnected(_)) => Err(writer_stopped()),
+        }
+    }
+
+    fn prune_at(&self, now: i64) -> Result<usize> {
+        self.conn()
+            .execute(
+                &format!("DELETE FROM ptr_cache WHERE NOT ({})", FRESH),
+                params![now, self.max_age_secs()],
+            )
+            .map_err(query_error)
+    }
+
+    fn stats_at(&self, now: i64) -> Result<CacheDbStats> {
+        let conn = self.conn();
+        let (entries, fresh, oldest, newest) = conn
+            .query_row(
+                &format!(
+                    "SELECT COUNT(*), COALESCE(SUM({}), 0), MIN(resolved_at), MAX(resolved_at)
+                     FROM ptr_cache",
+                    FRESH
+                ),
+                params![now, self.max_age_secs()],
+                |row| {
+                    Ok((
+                        row.get::<_, u64>(0)?,
+                        row.get::<_, u64>(1)?,
+                        row.get::<_, Option<i64>>(2)?,
+                        row.get::<_, Option<i64>>(3)?,
+                    ))
+                },
+            )
+            .map_err(query_error)?;
+
+        let mut statement = conn
+            .prepare(
+                "SELECT status, COUNT(*) AS n FROM ptr_cache GROUP BY status ORDER BY n DESC, status",
+            )
+            .map_err(query_error)?;
+        let statuses = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(query_error)?;
+
+        Ok(CacheDbStats {
+            entries,
+            fresh,
+            statuses,
+            oldest: oldest.map(timestamp),
+            newest: newest.map(timestamp),
+        })
+    }
+
+    fn max_age_secs(&self) -> Option<i64> {
+        self.max_age
+            .map(|age| i64::try_from(age.as_secs()).unwrap_or(i64::MAX))
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for CacheDb {
+    /// Commit the rows still queued
+    fn drop(&mut self) {
+        if let Some(Writer { queue, thread }) = self.writer.take() {
+            drop(queue);
+            let _ = thread.join();
+        }
+        if self.dropped() > 0 {
+            warn!(
+                "{} results were not stored in the cache database because its writer fell behind",
+                self.dropped()
+            );
+        }
+    }
+}
+
+/// Writer thread: commit queued rows in batches until the queue closes.
+/// A batch that fails is logged and dropped; the cache only loses entries.
+fn write_rows(mut conn: Connection, queue: Receiver<Queued>) {
+    let mut rows = Vec::with_capacity(BATCH_SIZE);
+    let mut flushes = Vec::new();
+    while let Ok(queued) = queue.recv() {
+        let mut next = Some(queued);
+        while let Some(queued) = next.take() {
+            match queued {
+                Queued::Row(row) => rows.push(row),
+                Queued::Flush(done) => flushes.push(done),
+            }
+            if rows.len() < BATCH_SIZE {
+                next = queue.try_recv().ok();
+            }
+        }
+
+        if let Err(e) = commit(&mut conn, &rows) {
+            warn!(
+                "Cannot store {} results in the cache database: {}",
+                rows.len(),
+                e
+            );
+        }
+        rows.clear();
+        for done in flushes.drain(..) {
+            let _ = done.send(());
+        }
+    }
+}
+
+fn commit(conn: &mut Connection, rows: &[Row]) -> rusqlite::Result<()> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+    let tx = conn.transaction()?;
+    {
+        let mut insert = tx.prepare_cached(
+            "INSERT OR REPLACE INTO ptr_cache (ip, status, ttl, resolved_at, result)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        for row in rows {
+            insert.execute(params![
+                row.ip,
+                row.status,
+                row.ttl,
+                row.resolved_at,
+                row.result
+            ])?;
+        }
+    }
+    tx.commit()
+}
+
+fn timestamp(secs: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(secs, 0).unwrap_or_default()
+}
+
+fn db_error(path: &Path, e: rusqlite::Error) -> ReverDNSError {
+    ReverDNSError::ConfigError(format!(
+        "Cannot open cache database {}: {}",
+        path.display(),
+        e
+    ))
+}
+
+fn query_error(e: rusqlite::Error) -> ReverDNSError {
+    ReverDNSError::InternalError(format!("Cache database error: {}", e))
+}
+
+fn writer_stopped() -> ReverDNSError {
+    ReverDNSError::InternalError("Cache database writer stopped".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::LookupStatus;
+    use tempfile::TempDir;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn result(status: LookupStatus, ttl: Option<u32>) -> LookupResult {
+        LookupResult {
+            cache: Some(CacheStatus::Miss),
+            ttl,
+            latency_ms: 20,
+            ..LookupResult::new("192.0.2.1", status, "192.0.2.53:53/udp")
+                .with_hostnames(["a.example"])
+        }
+    }
+
+    fn open(dir: &TempDir, max_age: Option<u64>) -> CacheDb {
+        CacheDb::open(
+            &dir.path().join("cache.db"),
+            max_age.map(Duration::from_secs),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_reused_while_ttl_lasts() {
+        let dir = TempDir::new().unwrap();
+        let db = open(&dir, None);
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+        db.insert_at(ip, &result(LookupStatus::Success, Some(300)), None, NOW)
+            .unwrap();
+        db.flush().unwrap();
+
+        let (hit, remaining) = db.get_at(ip, NOW + 100).unwrap().unwrap();
+        assert_eq!(hit.hostname.as_deref(), Some("a.example"));
+        assert_eq!(hit.cache, Some(CacheStatus::Hit));
+        assert_eq!(hit.ttl, Some(200));
+        assert_eq!(remaining, 200);
+        assert!(db.get_at(ip, NOW + 300).unwrap().is_none());
+
+        // Another run sees the same rows
+        drop(db);
+        let db = open(&dir, None);
+        assert!(db.get_at(ip, NOW + 299).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_max_age_replaces_ttl() {
+        let dir = TempDir::new().unwrap();
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+        open(&dir, None)
+            .insert_at(ip, &result(LookupStatus::Success, Some(300)), None, NOW)
+            .unwrap();
+
+        let db = open(&dir, Some(86400));
+        let (hit, _) = db.get_at(ip, NOW + 3600).unwrap().unwrap();
+        assert_eq!(hit.ttl, Some(0));
+        assert!(db.get_at(ip, NOW + 86401).unwrap().is_none());
+
+        let db = open(&dir, Some(60));
+        assert!(db.get_at(ip, NOW + 61).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_negative_and_failed_results() {
+        let dir = TempDir::new().unwrap();
+        let db = open(&dir, None);
+        let nxdomain: IpAddr = "192.0.2.1".parse().unwrap();
+        let timeout: IpAddr = "192.0.2.2".parse().unwrap();
+        db.insert_at(
+            nxdomain,
+            &result(LookupStatus::NxDomain, None),
+            Some(60),
+            NOW,
+        )
+        .unwrap();
+        db.insert_at(timeout, &result(LookupStatus::Timeout, None), None, NOW)
+            .unwrap();
+        db.flush().unwrap();
+
+        let (hit, remaining) = db.get_at(nxdomain, NOW + 30).unwrap().unwrap();
+        assert_eq!(hit.status, LookupStatus::NxDomain);
+        assert_eq!(remaining, 30);
+        assert!(db.get_at(timeout, NOW).unwrap().is_none());
+        assert_eq!(db.stats_at(NOW).unwrap().entries, 1);
+    }
+
+    #[test]
+    fn test_stats_prune_and_export() {
+        let dir = TempDir::new().unwrap();
+        let db = open(&dir, None);
+        for (i, ttl) in [60, 600, 6000].into_iter().enumerate() {
+            let ip = IpAddr::from([192, 0, 2, i as u8 + 1]);
+            db.insert_at(ip, &result(LookupStatus::Success, Some(ttl)), None, NOW)
+                .unwrap();
+        }
+        db.insert_at(
+            "192.0.2.9".parse().unwrap(),
+            &result(LookupStatus::NoData, None),
+            Some(60),
+            NOW + 10,
+        )
+        .unwrap();
+        db.flush().unwrap();
+
+        let stats = db.stats_at(NOW + 120).unwrap();
+        assert_eq!(stats.entries, 4);
+        assert_eq!(stats.fresh, 2);
+        assert_eq!(
+            stats.statuses,
+            vec![("success".to_string(), 3), ("nodata".to_string(), 1)]
+        );
+        assert_eq!(stats.oldest, Some(timestamp(NOW)));
+        assert_eq!(stats.newest, Some(timestamp(NOW + 10)));
+
+        assert_eq!(db.prune_at(NOW + 120).unwrap(), 2);
+        let mut out = Vec::new();
+        assert_eq!(db.export(&mut out).unwrap(), 2);
+        let lines: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines[0]["ttl"], 600);
+        assert_eq!(lines[0]["result"]["ip"], "192.0.2.1");
+        assert!(lines[0]["result"].get("cache").is_none());
+    }
+
+    #[test]
+    fn test_full_queue_drops_rows() {
+        let dir = TempDir::new().unwrap();
+        let db = open(&dir, None);
+        // Stall the writer on the database lock
+        let blocker = Connection::open(dir.path().join("cache.db")).unwrap();
+        blocker.execute_batch("BEGIN EXCLUSIVE").unwrap();
+
+        let inserted = QUEUE_SIZE + 2 * BATCH_SIZE;
+        for i in 0..inserted {
+            let ip = IpAddr::from([10, 0, (i / 256) as u8, (i % 256) as u8]);
+            db.insert_at(ip, &result(LookupStatus::Success, Some(300)), None, NOW)
+                .unwrap();
+        }
+        blocker.execute_batch("COMMIT").unwrap();
+        db.flush().unwrap();
+
+        assert!(db.dropped() > 0);
+        let stored = db.stats_at(NOW).unwrap().entries;
+        assert_eq!(stored + db.dropped(), inserted as u64);
+    }
+}
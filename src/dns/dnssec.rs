@@ -0,0 +1,750 @@
+use super::doh::DohClient;
+use super::transport;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::debug;
+use trust_dns_resolver::error::{ResolveError, ResolveErrorKind};
+use trust_dns_resolver::lookup::Lookup;
+use trust_dns_resolver::proto::error::ProtoErrorKind;
+use trust_dns_resolver::proto::op::Query;
+use trust_dns_resolver::proto::rr::dnssec::rdata::DNSSECRData;
+use trust_dns_resolver::proto::rr::dnssec::TrustAnchor;
+use trust_dns_resolver::proto::rr::{Name, RData, RecordType};
+use trust_dns_resolver::proto::xfer::{
+    DnsHandle, DnsRequest, DnsRequestOptions, DnsResponse, DnssecDnsHandle,
+};
+
+/// DNSSEC validation outcome of an answer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DnssecStatus {
+    /// Signed, with a chain of trust up to the root
+    Secure,
+    /// Not signed
+    Insecure,
+    /// Signed, but the signatures or the chain of trust do not verify
+    Bogus,
+    /// Validation could not complete, e.g. the server timed out or dropped
+    /// the DNSSEC records
+    Indeterminate,
+}
+
+impl std::fmt::Display for DnssecStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DnssecStatus::Secure => write!(f, "secure"),
+            DnssecStatus::Insecure => write!(f, "insecure"),
+            DnssecStatus::Bogus => write!(f, "bogus"),
+            DnssecStatus::Indeterminate => write!(f, "indeterminate"),
+        }
+    }
+}
+
+/// Messages the trust-dns validator fails with when signatures, keys or the
+/// chain of trust do not verify. They are only matched by text, so the
+/// trust-dns version is pinned and the tests produce each of them.
+const VERIFICATION_FAILURES: &[&str] = &[
+    "validation failed",
+    "Could not validate all DNSKEYs",
+    "self-signed dnskey is invalid",
+    "could not validate negative response missing SOA",
+    "could not validate negative response with NSEC",
+];
+
+/// Status of a validating lookup for `record_type`.
+///
+/// trust-dns only hands back records it could verify, and otherwise fails
+/// with the reason: the validator's RRSIG, DNSKEY and DS verification
+/// failures mean the answer is bogus. Missing signatures alone prove nothing,
+/// since anyone on the path can strip them, so they leave the outcome open
+/// like any other error until [`zone_cut`] shows the zone unsigned.
+pub(crate) fn classify(
+    result: &std::result::Result<Lookup, ResolveError>,
+    record_type: RecordType,
+) -> DnssecStatus {
+    let error = match result {
+        Ok(lookup) if lookup.record_iter().any(|r| r.record_type() == record_type) => {
+            return DnssecStatus::Secure
+        }
+        Ok(_) => return DnssecStatus::Indeterminate,
+        Err(e) => e,
+    };
+
+    match error.kind() {
+        _ if transport::is_truncated(error) => DnssecStatus::Indeterminate,
+        ResolveErrorKind::Proto(e) => match e.kind() {
+            ProtoErrorKind::Message(message) if VERIFICATION_FAILURES.contains(message) => {
+                DnssecStatus::Bogus
+            }
+            _ => DnssecStatus::Indeterminate,
+        },
+        _ => DnssecStatus::Indeterminate,
+    }
+}
+
+/// Whether `error` is the validator finding no signatures over a record set
+fn is_unsigned(error: &ResolveError) -> bool {
+    matches!(
+        error.kind(),
+        ResolveErrorKind::Proto(e) if matches!(e.kind(), ProtoErrorKind::RrsigsNotPresent { .. })
+    )
+}
+
+/// An answer together with the validator's verdict on it
+pub(crate) struct Validated {
+    /// The answer as the server sent it, whether or not it verified
+    pub answer: std::result::Result<Lookup, ResolveError>,
+    pub status: DnssecStatus,
+    /// Why the answer is not secure; empty if it is
+    pub reason: String,
+}
+
+/// Send `query` through `handle` with DNSSEC validation against the root
+/// trust anchor.
+///
+/// The query goes out once. Its answer is kept on the way into the
+/// validator, which only hands back records that verify, so unsigned answers
+/// are still reported and the status describes exactly those records. An
+/// unsigned answer is only insecure once validated DS queries prove its zone
+/// unsigned. `None` if no answer arrives within `timeout`; an answer whose
+/// keys or zone cut cannot be checked in time is indeterminate.
+pub(crate) async fn validated_lookup<H>(
+    handle: H,
+    query: Query,
+    timeout: Duration,
+) -> Option<Validated>
+where
+    H: DnsHandle<Error = ResolveError>,
+{
+    validate(handle, query, TrustAnchor::default(), timeout).await
+}
+
+async fn validate<H>(
+    handle: H,
+    query: Query,
+    trust_anchor: TrustAnchor,
+    timeout: Duration,
+) -> Option<Validated>
+where
+    H: DnsHandle<Error = ResolveError>,
+{
+    let deadline = tokio::time::Instant::now() + timeout;
+    let answer = Arc::new(Mutex::new(None));
+    let mut validator = DnssecDnsHandle::with_trust_anchor(
+        Tap {
+            handle: handle.clone(),
+            query: query.clone(),
+            answer: answer.clone(),
+        },
+        trust_anchor.clone(),
+    );
+
+    let mut responses = validator.lookup(query.clone(), request_options());
+    let (verified, timed_out) = match tokio::time::timeout_at(deadline, responses.next()).await {
+        Ok(Some(response)) => (
+            response.and_then(|r| DohClient::answer(query.clone(), r)),
+            false,
+        ),
+        Ok(None) => (Err(ResolveError::from("no response to verify")), false),
+        Err(_) => (Err(ResolveErrorKind::Timeout.into()), true),
+    };
+
+    let answer = answer.lock().unwrap_or_else(|e| e.into_inner()).take();
+    let answer = match answer {
+        Some(answer) => answer.and_then(|r| DohClient::answer(query.clone(), r)),
+        None if timed_out => return None,
+        // The validator gave up before sending anything
+        None => verified.clone(),
+    };
+    let mut status = classify(&verified, query.query_type());
+    let mut reason = verified
+        .as_ref()
+        .err()
+        .map(|e| e.to_string())
+        .unwrap_or_default();
+    // Records came back without signatures: whether they should have had any
+    // decides between insecure and bogus
+    if answer.is_ok() && matches!(&verified, Err(e) if is_unsigned(e)) {
+        let cut = zone_cut(handle, &trust_anchor, query.name());
+        match tokio::time::timeout_at(deadline, cut).await {
+            Ok(ZoneCut::Unsigned(cut)) => {
+                debug!("{} is under unsigned delegation {}", query.name(), cut);
+                status = DnssecStatus::Insecure;
+            }
+            Ok(ZoneCut::Signed(zone)) => {
+                status = DnssecStatus::Bogus;
+                reason = format!("unsigned records in signed zone {}", zone);
+            }
+            Ok(ZoneCut::Unknown) => reason = format!("{}; zone not proven unsigned", reason),
+            Err(_) => reason = format!("{}; timed out proving the zone unsigned", reason),
+        }
+    }
+    Some(Validated {
+        answer,
+        status,
+        reason,
+    })
+}
+
+fn request_options() -> DnsRequestOptions {
+    let mut options = DnsRequestOptions::default();
+    // Signatures and keys rarely fit in 512 bytes
+    options.use_edns = true;
+    options
+}
+
+/// Where an unsigned answer's zone hangs off the signed tree
+enum ZoneCut {
+    /// DS records for this zone verify, so its records must be signed
+    Signed(Name),
+    /// A verified NSEC record proves this delegation has no DS records
+    Unsigned(Name),
+    /// Neither could be proven
+    Unknown,
+}
+
+/// Find the zone cut above `owner` with validated DS queries, from `owner`
+/// up. Names that are not delegations, and DS answers that are themselves
+/// unsigned (the parent zone is unsigned too, or stripped), move the search
+/// up a label. Only NSEC proofs are understood; NSEC3 leaves it unknown.
+async fn zone_cut<H>(handle: H, trust_anchor: &TrustAnchor, owner: &Name) -> ZoneCut
+where
+    H: DnsHandle<Error = ResolveError>,
+{
+    let mut name = owner.clone();
+    while !name.is_root() {
+        let query = Query::query(name.clone(), RecordType::DS);
+        let mut validator =
+            DnssecDnsHandle::with_trust_anchor(handle.clone(), trust_anchor.clone());
+        match validator.lookup(query, request_options()).next().await {
+            Some(Ok(response)) => {
+                let signed = response
+                    .answers()
+                    .iter()
+                    .any(|r| r.record_type() == RecordType::DS && r.name() == &name);
+                if signed {
+                    return ZoneCut::Signed(name);
+                }
+                let delegation = response
+                    .name_servers()
+                    .iter()
+                    .filter(|r| r.name() == &name)
+                    .any(|r| match r.data() {
+                        Some(RData::DNSSEC(DNSSECRData::NSEC(nsec))) => {
+                            let types = nsec.type_bit_maps();
+                            types.contains(&RecordType::NS)
+                                && !types.contains(&RecordType::DS)
+                                && !types.contains(&RecordType::SOA)
+                        }
+                        _ => false,
+                    });
+                if delegation {
+                    return ZoneCut::Unsigned(name);
+                }
+            }
+            Some(Err(e)) if is_unsigned(&e) => {}
+            _ => return ZoneCut::Unknown,
+        }
+        name = name.base_name();
+    }
+    ZoneCut::Unknown
+}
+
+/// Passes requests through to `handle`, keeping the first response to
+/// `query` while the validator sends its own key and DS queries alongside
+#[derive(Clone)]
+struct Tap<H> {
+    handle: H,
+    query: Query,
+    answer: Arc<Mutex<Option<std::result::Result<DnsResponse, ResolveError>>>>,
+}
+
+impl<H: DnsHandle<Error = ResolveError>> DnsHandle for Tap<H> {
+    type Response = BoxStream<'static, std::result::Result<DnsResponse, ResolveError>>;
+    type Error = ResolveError;
+
+    fn send<R: Into<DnsRequest> + Unpin + Send + 'static>(&mut self, request: R) -> Self::Response {
+        let request = request.into();
+        let tapped = request.queries().first().is_some_and(|q| {
+            q.name() == self.query.name() && q.query_type() == self.query.query_type()
+        });
+        let responses = self.handle.send(request);
+        if !tapped {
+            return responses.boxed();
+        }
+
+        let answer = self.answer.clone();
+        responses
+            .inspect(move |response| {
+                answer
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .get_or_insert_with(|| response.clone());
+            })
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use std::collections::HashMap;
+    use trust_dns_resolver::proto::error::ProtoError;
+    use trust_dns_resolver::proto::op::{Message, MessageType, ResponseCode};
+    use trust_dns_resolver::proto::rr::dnssec::rdata::{DNSKEY, NSEC, RRSIG};
+    use trust_dns_resolver::proto::rr::dnssec::tbs::rrset_tbs;
+    use trust_dns_resolver::proto::rr::dnssec::{
+        Algorithm, DigestType, KeyFormat, KeyPair, Private,
+    };
+    use trust_dns_resolver::proto::rr::rdata::{PTR, SOA};
+    use trust_dns_resolver::proto::rr::{DNSClass, Record};
+
+    /// Zone delegated from the test root with DS records
+    const SIGNED_ZONE: &str = "2.0.192.in-addr.arpa.";
+    const SIGNED_OWNER: &str = "7.2.0.192.in-addr.arpa.";
+    /// Zone delegated from the test root without DS records
+    const UNSIGNED_ZONE: &str = "3.0.192.in-addr.arpa.";
+    const UNSIGNED_OWNER: &str = "7.3.0.192.in-addr.arpa.";
+    /// Name in [`SIGNED_ZONE`] without records
+    const MISSING_OWNER: &str = "9.2.0.192.in-addr.arpa.";
+
+    fn proto(kind: ProtoErrorKind) -> std::result::Result<Lookup, ResolveError> {
+        Err(ProtoError::from(kind).into())
+    }
+
+    fn name(name: &str) -> Name {
+        Name::from_ascii(name).unwrap()
+    }
+
+    fn record(owner: &str, rdata: RData) -> Record {
+        Record::from_rdata(name(owner), 300, rdata)
+    }
+
+    fn soa(zone: &str) -> Record {
+        let soa = SOA::new(
+            name("ns.test."),
+            name("hostmaster.test."),
+            1,
+            3600,
+            600,
+            86400,
+            60,
+        );
+        record(zone, RData::SOA(soa))
+    }
+
+    fn nsec(owner: &str, next: &str, types: Vec<RecordType>) -> Record {
+        record(
+            owner,
+            RData::DNSSEC(DNSSECRData::NSEC(NSEC::new(name(next), types))),
+        )
+    }
+
+    fn ptr(owner: &str) -> Record {
+        record(owner, RData::PTR(PTR(name("ptr.test."))))
+    }
+
+    /// Signing key of `zone`
+    struct Key {
+        zone: Name,
+        pair: KeyPair<Private>,
+        dnskey: DNSKEY,
+    }
+
+    impl Key {
+        fn new(zone: &str) -> Self {
+            let pkcs8 = KeyPair::generate_pkcs8(Algorithm::ED25519).unwrap();
+            let pair = KeyFormat::Pkcs8
+                .decode_key(&pkcs8, None, Algorithm::ED25519)
+                .unwrap();
+            let dnskey = pair.to_dnskey(Algorithm::ED25519).unwrap();
+            Self {
+                zone: name(zone),
+                pair,
+                dnskey,
+            }
+        }
+
+        /// `records`, one record set, followed by this key's RRSIG over them
+        fn sign(&self, mut records: Vec<Record>) -> Vec<Record> {
+            let (owner, record_type) = (records[0].name().clone(), records[0].record_type());
+            let key_tag = self.dnskey.calculate_key_tag().unwrap();
+            let tbs = rrset_tbs(
+                &owner,
+                DNSClass::IN,
+                owner.num_labels(),
+                record_type,
+                Algorithm::ED25519,
+                300,
+                u32::MAX,
+                0,
+                key_tag,
+                &self.zone,
+                &records,
+            )
+            .unwrap();
+            let rrsig = RRSIG::new(
+                record_type,
+                Algorithm::ED25519,
+                owner.num_labels(),
+                300,
+                u32::MAX,
+                0,
+                key_tag,
+                self.zone.clone(),
+                self.pair.sign(Algorithm::ED25519, &tbs).unwrap(),
+            );
+            records.push(Record::from_rdata(
+                owner,
+                300,
+                RData::DNSSEC(DNSSECRData::RRSIG(rrsig)),
+            ));
+            records
+        }
+
+        fn dnskey(&self) -> Vec<Record> {
+            let dnskey = RData::DNSSEC(DNSSECRData::DNSKEY(self.dnskey.clone()));
+            self.sign(vec![Record::from_rdata(self.zone.clone(), 300, dnskey)])
+        }
+
+        fn ds(&self) -> Record {
+            let ds = self
+                .pair
+                .to_ds(&self.zone, Algorithm::ED25519, DigestType::SHA256)
+                .unwrap();
+            Record::from_rdata(self.zone.clone(), 300, RData::DNSSEC(DNSSECRData::DS(ds)))
+        }
+    }
+
+    fn unsigned(records: Vec<Record>) -> Vec<Record> {
+        records
+            .into_iter()
+            .filter(|r| r.record_type() != RecordType::RRSIG)
+            .collect()
+    }
+
+    #[derive(Clone, Default)]
+    struct Response {
+        code: ResponseCode,
+        answers: Vec<Record>,
+        authority: Vec<Record>,
+    }
+
+    fn answer(answers: Vec<Record>) -> Response {
+        Response {
+            answers,
+            ..Response::default()
+        }
+    }
+
+    fn denial(code: ResponseCode, authority: Vec<Record>) -> Response {
+        Response {
+            code,
+            authority,
+            ..Response::default()
+        }
+    }
+
+    /// Test hierarchy served from memory: a root signed with its own trust
+    /// anchor, delegating [`SIGNED_ZONE`] with DS records and
+    /// [`UNSIGNED_ZONE`] without
+    struct Hierarchy {
+        trust_anchor: TrustAnchor,
+        root: Key,
+        signed: Key,
+        responses: HashMap<(Name, RecordType), Response>,
+    }
+
+    impl Hierarchy {
+        fn new() -> Self {
+            let root = Key::new(".");
+            let signed = Key::new(SIGNED_ZONE);
+            let mut trust_anchor = TrustAnchor::new();
+            trust_anchor.insert_trust_anchor(&root.pair.to_public_key().unwrap());
+
+            let mut hierarchy = Self {
+                trust_anchor,
+                responses: HashMap::new(),
+                root,
+                signed,
+            };
+            let root = &hierarchy.root;
+            let signed = &hierarchy.signed;
+            let responses = [
+                ((".", RecordType::DNSKEY), answer(root.dnskey())),
+                ((SIGNED_ZONE, RecordType::DNSKEY), answer(signed.dnskey())),
+                (
+                    (SIGNED_ZONE, RecordType::DS),
+                    answer(root.sign(vec![signed.ds()])),
+                ),
+                (
+                    (SIGNED_OWNER, RecordType::PTR),
+                    answer(signed.sign(vec![ptr(SIGNED_OWNER)])),
+                ),
+                (
+                    (SIGNED_OWNER, RecordType::DS),
+                    denial(
+                        ResponseCode::NoError,
+                        [
+                            signed.sign(vec![soa(SIGNED_ZONE)]),
+                            signed.sign(vec![nsec(
+                                SIGNED_OWNER,
+                                "8.2.0.192.in-addr.arpa.",
+                                vec![RecordType::PTR, RecordType::RRSIG, RecordType::NSEC],
+                            )]),
+                        ]
+                        .concat(),
+                    ),
+                ),
+                (
+                    (UNSIGNED_ZONE, RecordType::DS),
+                    denial(
+                        ResponseCode::NoError,
+                        [
+                            root.sign(vec![soa(".")]),
+                            root.sign(vec![nsec(
+                                UNSIGNED_ZONE,
+                                "4.0.192.in-addr.arpa.",
+                                vec![RecordType::NS, RecordType::RRSIG, RecordType::NSEC],
+                            )]),
+                        ]
+                        .concat(),
+                    ),
+                ),
+                (
+                    (UNSIGNED_OWNER, RecordType::PTR),
+                    answer(vec![ptr(UNSIGNED_OWNER)]),
+                ),
+                (
+                    (UNSIGNED_OWNER, RecordType::DS),
+                    denial(ResponseCode::NoError, vec![soa(UNSIGNED_ZONE)]),
+                ),
+            ]
+            .into_iter()
+            .map(|((owner, record_type), response)| ((name(owner), record_type), response))
+            .collect();
+            hierarchy.responses = responses;
+            hierarchy
+        }
+
+        fn set(&mut self, owner: &str, record_type: RecordType, response: Response) {
+            self.responses.insert((name(owner), record_type), response);
+        }
+
+        /// Validate a PTR query for `owner`
+        async fn validate(&self, owner: &str) -> Validated {
+            let servers = Servers(Arc::new(self.responses.clone()));
+            let query = Query::query(name(owner), RecordType::PTR);
+            validate(
+                servers,
+                query,
+                self.trust_anchor.clone(),
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap()
+        }
+    }
+
+    /// Answers every question from canned responses, and with an empty
+    /// NOERROR response otherwise
+    #[derive(Clone)]
+    struct Servers(Arc<HashMap<(Name, RecordType), Response>>);
+
+    impl DnsHandle for Servers {
+        type Response = BoxStream<'static, std::result::Result<DnsResponse, ResolveError>>;
+        type Error = ResolveError;
+
+        fn send<R: Into<DnsRequest> + Unpin + Send + 'static>(
+            &mut self,
+            request: R,
+        ) -> Self::Response {
+            let request = request.into();
+            let query = request.queries()[0].clone();
+            let canned = self
+                .0
+                .get(&(query.name().clone(), query.query_type()))
+                .cloned()
+                .unwrap_or_default();
+            let mut message = Message::new();
+            message
+                .set_id(request.id())
+                .set_message_type(MessageType::Response)
+                .set_response_code(canned.code)
+                .add_query(query)
+                .add_answers(canned.answers)
+                .add_name_servers(canned.authority);
+            let response = DnsResponse::from_message(message).map_err(ResolveError::from);
+            futures::stream::once(future::ready(response)).boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_signed_answer() {
+        let validated = Hierarchy::new().validate(SIGNED_OWNER).await;
+        assert_eq!(validated.status, DnssecStatus::Secure);
+        assert!(validated.answer.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_stripped_signatures_are_bogus() {
+        let mut hierarchy = Hierarchy::new();
+        let stripped = unsigned(hierarchy.signed.sign(vec![ptr(SIGNED_OWNER)]));
+        hierarchy.set(SIGNED_OWNER, RecordType::PTR, answer(stripped));
+
+        let validated = hierarchy.validate(SIGNED_OWNER).await;
+        assert_eq!(validated.status, DnssecStatus::Bogus);
+        assert!(
+            validated.reason.contains(SIGNED_ZONE),
+            "{}",
+            validated.reason
+        );
+        // The records are still reported, for review
+        assert!(validated.answer.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_insecure_delegation() {
+        let validated = Hierarchy::new().validate(UNSIGNED_OWNER).await;
+        assert_eq!(validated.status, DnssecStatus::Insecure);
+        assert!(validated.answer.is_ok());
+    }
+
+    /// Without an authenticated denial of DS records, an unsigned answer
+    /// proves nothing either way
+    #[tokio::test]
+    async fn test_unproven_unsigned_answer() {
+        let mut hierarchy = Hierarchy::new();
+        let root = &hierarchy.root;
+        let stripped = [
+            root.sign(vec![soa(".")]),
+            unsigned(vec![nsec(
+                UNSIGNED_ZONE,
+                "4.0.192.in-addr.arpa.",
+                vec![RecordType::NS, RecordType::RRSIG, RecordType::NSEC],
+            )]),
+        ]
+        .concat();
+        hierarchy.set(
+            UNSIGNED_ZONE,
+            RecordType::DS,
+            denial(ResponseCode::NoError, stripped),
+        );
+
+        let validated = hierarchy.validate(UNSIGNED_OWNER).await;
+        assert_eq!(validated.status, DnssecStatus::Indeterminate);
+        assert!(validated.answer.is_ok());
+    }
+
+    /// Changes to the hierarchy, given a key that is trusted nowhere
+    type Scenario = fn(&mut Hierarchy, &Key);
+
+    /// Each verification failure the list matches is produced by the pinned
+    /// trust-dns validator, word for word
+    #[tokio::test]
+    async fn test_verification_failures() {
+        let rogue = Key::new(SIGNED_ZONE);
+        let scenarios: Vec<(&str, Scenario)> = vec![
+            (SIGNED_OWNER, |h, rogue| {
+                h.set(
+                    SIGNED_OWNER,
+                    RecordType::PTR,
+                    answer(rogue.sign(vec![ptr(SIGNED_OWNER)])),
+                );
+            }),
+            (SIGNED_OWNER, |h, rogue| {
+                let ds = h.root.sign(vec![rogue.ds()]);
+                h.set(SIGNED_ZONE, RecordType::DS, answer(ds));
+            }),
+            (SIGNED_OWNER, |h, rogue| {
+                let mut dnskey = unsigned(h.signed.dnskey());
+                dnskey.push(rogue.sign(dnskey.clone()).pop().unwrap());
+                h.set(SIGNED_ZONE, RecordType::DNSKEY, answer(dnskey));
+            }),
+            (MISSING_OWNER, |h, _| {
+                let nsec = h.signed.sign(vec![nsec(
+                    "8.2.0.192.in-addr.arpa.",
+                    "a.2.0.192.in-addr.arpa.",
+                    vec![RecordType::PTR, RecordType::RRSIG, RecordType::NSEC],
+                )]);
+                h.set(
+                    MISSING_OWNER,
+                    RecordType::PTR,
+                    denial(ResponseCode::NXDomain, nsec),
+                );
+            }),
+            (MISSING_OWNER, |h, _| {
+                let soa = h.signed.sign(vec![soa(SIGNED_ZONE)]);
+                h.set(
+                    MISSING_OWNER,
+                    RecordType::PTR,
+                    denial(ResponseCode::NXDomain, soa),
+                );
+            }),
+        ];
+        assert_eq!(scenarios.len(), VERIFICATION_FAILURES.len());
+
+        for ((owner, scenario), message) in scenarios.into_iter().zip(VERIFICATION_FAILURES) {
+            let mut hierarchy = Hierarchy::new();
+            scenario(&mut hierarchy, &rogue);
+            let validated = hierarchy.validate(owner).await;
+            assert!(validated.reason.ends_with(message), "{}", validated.reason);
+            assert_eq!(validated.status, DnssecStatus::Bogus, "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        let unsigned = proto(ProtoErrorKind::RrsigsNotPresent {
+            name: Name::from_ascii("7.2.0.192.in-addr.arpa.").unwrap(),
+            record_type: RecordType::PTR,
+        });
+        // Only a proven unsigned delegation makes this insecure
+        assert_eq!(
+            classify(&unsigned, RecordType::PTR),
+            DnssecStatus::Indeterminate
+        );
+        assert_eq!(
+            classify(
+                &proto(ProtoErrorKind::Message("validation failed")),
+                RecordType::PTR
+            ),
+            DnssecStatus::Bogus
+        );
+        assert_eq!(
+            classify(
+                &proto(ProtoErrorKind::Message("Could not validate all DNSKEYs")),
+                RecordType::PTR
+            ),
+            DnssecStatus::Bogus
+        );
+        // Generic protocol errors are not verification failures
+        assert_eq!(
+            classify(
+                &proto(ProtoErrorKind::Message("no connections available")),
+                RecordType::PTR
+            ),
+            DnssecStatus::Indeterminate
+        );
+        assert_eq!(
+            classify(
+                &proto(ProtoErrorKind::Msg("validation failed".to_string())),
+                RecordType::PTR
+            ),
+            DnssecStatus::Indeterminate
+        );
+        assert_eq!(
+            classify(&proto(ProtoErrorKind::Timeout), RecordType::PTR),
+            DnssecStatus::Indeterminate
+        );
+        assert_eq!(
+            classify(&Err(ResolveErrorKind::Timeout.into()), RecordType::PTR),
+            DnssecStatus::Indeterminate
+        );
+        assert_eq!(DnssecStatus::Indeterminate.to_string(), "indeterminate");
+    }
+}
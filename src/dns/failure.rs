@@ -0,0 +1,182 @@
+use super::resolver::LookupStatus;
+use super::transport;
+use crate::error::ReverDNSError;
+use trust_dns_resolver::error::{ResolveError, ResolveErrorKind};
+use trust_dns_resolver::proto::error::ProtoErrorKind;
+use trust_dns_resolver::proto::op::ResponseCode;
+
+/// How a failed query is reported, and what it says about the server
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Failure {
+    pub status: LookupStatus,
+    /// Error recorded in the result
+    pub message: String,
+    /// The server sent a well-formed response, so it counts as healthy
+    pub answered: bool,
+    /// Asking again, possibly another server, may give a different answer
+    pub retryable: bool,
+    /// How long the absence of records may be cached, from the SOA record
+    /// of an NXDOMAIN or NODATA response
+    pub negative_ttl: Option<u32>,
+}
+
+impl Failure {
+    /// No response within the lookup timeout
+    pub(crate) fn timeout() -> Self {
+        Self {
+            status: LookupStatus::Timeout,
+            message: "Timeout".to_string(),
+            answered: false,
+            retryable: true,
+            negative_ttl: None,
+        }
+    }
+
+    /// The query was not admitted by the rate limiter or the upstream's
+    /// own limits
+    pub(crate) fn rate_limited(error: &ReverDNSError) -> Self {
+        Self {
+            status: LookupStatus::RateLimited,
+            message: error.to_string(),
+            answered: false,
+            retryable: error.is_retryable(),
+            negative_ttl: None,
+        }
+    }
+
+    /// Classify an error from the resolver by its kind and response code
+    pub(crate) fn classify(error: &ResolveError) -> Self {
+        match error.kind() {
+            ResolveErrorKind::NoRecordsFound {
+                response_code,
+                negative_ttl,
+                ..
+            } => Self::from_response_code(*response_code, *negative_ttl),
+            // The server answered, just not in full; asking again over UDP
+            // gets the same truncated response
+            _ if transport::is_truncated(error) => Self {
+                status: LookupStatus::Failed,
+                message: error.to_string(),
+                answered: true,
+                retryable: false,
+                negative_ttl: None,
+            },
+            ResolveErrorKind::Timeout => Self::timeout(),
+            ResolveErrorKind::Io(_) | ResolveErrorKind::NoConnections => {
+                Self::network(error.to_string())
+            }
+            ResolveErrorKind::Proto(e) => match e.kind() {
+                ProtoErrorKind::Timeout => Self::timeout(),
+                ProtoErrorKind::Io(_)
+                | ProtoErrorKind::Busy
+                | ProtoErrorKind::Canceled(_)
+                | ProtoErrorKind::RustlsError(_) => Self::network(error.to_string()),
+                _ => Self::other(error.to_string()),
+            },
+            _ => Self::other(error.to_string()),
+        }
+    }
+
+    fn from_response_code(code: ResponseCode, negative_ttl: Option<u32>) -> Self {
+        let (status, message, retryable) = match code {
+            // Definite answers, though a server with a stale or partial
+            // view of the zone may be wrong; the retry policy leaves them
+            // alone unless told otherwise
+            ResponseCode::NXDomain => (LookupStatus::NxDomain, "NXDOMAIN".to_string(), true),
+            ResponseCode::NoError => (LookupStatus::NoData, "NODATA".to_string(), true),
+            // The server could not or would not resolve the name; another
+            // server may
+            ResponseCode::ServFail => (LookupStatus::ServFail, "SERVFAIL".to_string(), true),
+            ResponseCode::Refused => (LookupStatus::Refused, "REFUSED".to_string(), true),
+            code => (
+                LookupStatus::Failed,
+                format!("Server responded with {} ({})", code, u16::from(code)),
+                true,
+            ),
+        };
+        Self {
+            status,
+            message,
+            answered: !matches!(status, LookupStatus::Refused),
+            retryable,
+            negative_ttl,
+        }
+    }
+
+    fn network(message: String) -> Self {
+        Self {
+            status: LookupStatus::NetworkError,
+            message,
+            answered: false,
+            retryable: true,
+            negative_ttl: None,
+        }
+    }
+
+    fn other(message: String) -> Self {
+        Self {
+            status: LookupStatus::Failed,
+            message,
+            answered: false,
+            retryable: true,
+            negative_ttl: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use trust_dns_resolver::proto::error::ProtoError;
+    use trust_dns_resolver::proto::op::Query;
+
+    fn no_records(response_code: ResponseCode) -> ResolveError {
+        ResolveErrorKind::NoRecordsFound {
+            query: Box::new(Query::new()),
+            soa: None,
+            negative_ttl: Some(60),
+            response_code,
+            trusted: true,
+        }
+        .into()
+    }
+
+    #[test]
+    fn test_classify_response_codes() {
+        let nxdomain = Failure::classify(&no_records(ResponseCode::NXDomain));
+        assert_eq!(nxdomain.status, LookupStatus::NxDomain);
+        assert_eq!(nxdomain.message, "NXDOMAIN");
+        assert!(nxdomain.answered && nxdomain.retryable);
+        assert_eq!(nxdomain.negative_ttl, Some(60));
+
+        let nodata = Failure::classify(&no_records(ResponseCode::NoError));
+        assert_eq!(nodata.status, LookupStatus::NoData);
+        assert!(nodata.answered);
+
+        let servfail = Failure::classify(&no_records(ResponseCode::ServFail));
+        assert_eq!(servfail.status, LookupStatus::ServFail);
+        assert!(servfail.answered && servfail.retryable);
+
+        let refused = Failure::classify(&no_records(ResponseCode::Refused));
+        assert_eq!(refused.status, LookupStatus::Refused);
+        assert!(!refused.answered && refused.retryable);
+
+        let notimp = Failure::classify(&no_records(ResponseCode::NotImp));
+        assert_eq!(notimp.status, LookupStatus::Failed);
+        assert_eq!(notimp.message, "Server responded with Not Implemented (4)");
+    }
+
+    #[test]
+    fn test_classify_transport_errors() {
+        let io = std::io::Error::from(std::io::ErrorKind::ConnectionRefused);
+        let failure = Failure::classify(&ResolveErrorKind::Io(io).into());
+        assert_eq!(failure.status, LookupStatus::NetworkError);
+        assert!(!failure.answered && failure.retryable);
+
+        let failure = Failure::classify(&ProtoError::from(ProtoErrorKind::Timeout).into());
+        assert_eq!(failure, Failure::timeout());
+
+        let failure = Failure::classify(&ResolveErrorKind::Msg("odd".to_string()).into());
+        assert_eq!(failure.status, LookupStatus::Failed);
+    }
+}
@@ -0,0 +1,288 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// When an upstream is taken out of rotation and how it is brought back.
+///
+/// After `failure_threshold` consecutive failures a server is ejected. Once
+/// `cooldown` has passed it receives a single probe query; a successful
+/// answer restores it, a failure doubles the cool-down (up to
+/// `max_cooldown`). Probing is passive: the probe is the next lookup sent
+/// after the cool-down, so a server only recovers while there is traffic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HealthPolicy {
+    /// Consecutive failures that eject a server; 0 disables ejection
+    pub failure_threshold: u32,
+    pub cooldown: Duration,
+    pub max_cooldown: Duration,
+}
+
+impl Default for HealthPolicy {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cooldown: Duration::from_secs(30),
+            max_cooldown: Duration::from_secs(300),
+        }
+    }
+}
+
+/// Whether an upstream is currently in rotation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthState {
+    Healthy,
+    Ejected,
+}
+
+impl std::fmt::Display for HealthState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HealthState::Healthy => write!(f, "healthy"),
+            HealthState::Ejected => write!(f, "ejected"),
+        }
+    }
+}
+
+/// Point-in-time health of one upstream, as reported in statistics
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpstreamHealth {
+    pub resolver: String,
+    pub state: HealthState,
+    pub successes: u64,
+    pub failures: u64,
+    pub consecutive_failures: u32,
+    /// Number of times the server was taken out of rotation
+    pub ejections: u32,
+    pub average_latency_ms: Option<f64>,
+}
+
+impl UpstreamHealth {
+    /// Queries that got an answer or failed
+    pub fn queries(&self) -> u64 {
+        self.successes + self.failures
+    }
+
+    /// Percentage of answered queries
+    pub fn success_rate(&self) -> f64 {
+        if self.queries() == 0 {
+            0.0
+        } else {
+            (self.successes as f64 / self.queries() as f64) * 100.0
+        }
+    }
+}
+
+/// Outcome counters and circuit breaker for one upstream
+#[derive(Debug)]
+pub struct Health {
+    policy: HealthPolicy,
+    inner: Mutex<Counters>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    successes: u64,
+    failures: u64,
+    consecutive_failures: u32,
+    ejections: u32,
+    /// Moving average of response times in microseconds; 0 until measured
+    latency_us: u64,
+    /// Set while ejected: when the next probe may be sent
+    ejected_until: Option<Instant>,
+    cooldown: Duration,
+    /// A probe was sent since the last ejection or failed probe
+    probing: bool,
+}
+
+impl Health {
+    pub fn new(policy: HealthPolicy) -> Self {
+        Self {
+            policy,
+            inner: Mutex::new(Counters::default()),
+        }
+    }
+
+    fn counters(&self) -> std::sync::MutexGuard<'_, Counters> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Record an answered query. Returns true if this brought an ejected
+    /// server back into rotation, which only a probe's answer does; answers
+    /// to queries sent before the ejection do not count.
+    pub fn record_success(&self, latency: Duration) -> bool {
+        let mut c = self.counters();
+        c.successes += 1;
+        c.consecutive_failures = 0;
+
+        let sample = (latency.as_micros() as u64).max(1);
+        c.latency_us = match c.latency_us {
+            0 => sample,
+            // Lost precision is irrelevant at microsecond resolution
+            previous => ((previous * 4 + sample) / 5).max(1),
+        };
+
+        if !c.probing {
+            return false;
+        }
+        c.probing = false;
+        c.ejected_until.take().is_some()
+    }
+
+    /// Record a query that timed out or failed without an answer. Returns
+    /// true if this took the server out of rotation.
+    pub fn record_failure(&self) -> bool {
+        let mut c = self.counters();
+        c.failures += 1;
+        c.consecutive_failures += 1;
+
+        if self.policy.failure_threshold == 0 {
+            return false;
+        }
+
+        let now = Instant::now();
+        if c.ejected_until.is_some() {
+            // Failed probe: stay out for longer. Failures of queries that
+            // were already in flight at ejection do not count.
+            if c.probing {
+                c.probing = false;
+                c.cooldown = (c.cooldown * 2).min(self.policy.max_cooldown);
+                c.ejected_until = Some(now + c.cooldown);
+            }
+            false
+        } else if c.consecutive_failures >= self.policy.failure_threshold {
+            c.ejections += 1;
+            c.cooldown = self.policy.cooldown;
+            c.ejected_until = Some(now + c.cooldown);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Whether the server is in rotation
+    pub fn is_healthy(&self) -> bool {
+        self.counters().ejected_until.is_none()
+    }
+
+    /// Claim the probe of an ejected server whose cool-down has passed. At
+    /// most one probe is handed out per cool-down period.
+    pub fn try_probe(&self) -> bool {
+        let mut c = self.counters();
+        match c.ejected_until {
+            Some(until) if Instant::now() >= until => {
+                c.probing = true;
+                c.ejected_until = Some(Instant::now() + c.cooldown);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Recent response time, if any query has been answered
+    pub fn latency(&self) -> Option<Duration> {
+        match self.counters().latency_us {
+            0 => None,
+            us => Some(Duration::from_micros(us)),
+        }
+    }
+
+    pub fn snapshot(&self, resolver: &str) -> UpstreamHealth {
+        let c = self.counters();
+        UpstreamHealth {
+            resolver: resolver.to_string(),
+            state: if c.ejected_until.is_some() {
+                HealthState::Ejected
+            } else {
+                HealthState::Healthy
+            },
+            successes: c.successes,
+            failures: c.failures,
+            consecutive_failures: c.consecutive_failures,
+            ejections: c.ejections,
+            average_latency_ms: match c.latency_us {
+                0 => None,
+                us => Some(us as f64 / 1000.0),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(cooldown_ms: u64) -> HealthPolicy {
+        HealthPolicy {
+            failure_threshold: 3,
+            cooldown: Duration::from_millis(cooldown_ms),
+            max_cooldown: Duration::from_millis(cooldown_ms * 4),
+        }
+    }
+
+    #[test]
+    fn test_ejects_after_consecutive_failures() {
+        let health = Health::new(policy(1000));
+        assert!(!health.record_failure());
+        assert!(!health.record_failure());
+        health.record_success(Duration::from_millis(10));
+        assert!(!health.record_failure());
+        assert!(!health.record_failure());
+        assert!(health.is_healthy());
+        assert!(health.record_failure());
+        assert!(!health.is_healthy());
+        // Not probed before the cool-down ends
+        assert!(!health.try_probe());
+
+        let report = health.snapshot("192.0.2.1:53/udp");
+        assert_eq!(report.state, HealthState::Ejected);
+        assert_eq!(report.failures, 5);
+        assert_eq!(report.ejections, 1);
+        assert_eq!(report.average_latency_ms, Some(10.0));
+    }
+
+    #[tokio::test]
+    async fn test_probe_recovers_or_backs_off() {
+        let health = Health::new(policy(20));
+        for _ in 0..3 {
+            health.record_failure();
+        }
+        tokio::time::sleep(Duration::from_millis(25)).await;
+
+        // One probe per cool-down; a failed probe extends the cool-down
+        assert!(health.try_probe());
+        assert!(!health.try_probe());
+        health.record_failure();
+        tokio::time::sleep(Duration::from_millis(25)).await;
+        assert!(!health.try_probe());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(health.try_probe());
+
+        assert!(health.record_success(Duration::from_millis(5)));
+        assert!(health.is_healthy());
+    }
+
+    #[test]
+    fn test_late_answer_does_not_recover() {
+        let health = Health::new(policy(1000));
+        for _ in 0..3 {
+            health.record_failure();
+        }
+        // Answer to a query that was in flight at ejection
+        assert!(!health.record_success(Duration::from_millis(5)));
+        assert!(!health.is_healthy());
+    }
+
+    #[test]
+    fn test_threshold_zero_never_ejects() {
+        let health = Health::new(HealthPolicy {
+            failure_threshold: 0,
+            ..HealthPolicy::default()
+        });
+        for _ in 0..100 {
+            assert!(!health.record_failure());
+        }
+        assert!(health.is_healthy());
+    }
+}
@@ -1,3 +1,27 @@
+pub mod cache;
+pub mod cache_db;
+pub mod dnssec;
+pub mod doh;
+pub mod failure;
+pub mod health;
+pub mod rate_limiter;
 pub mod resolver;
+pub mod retry;
+pub mod strategy;
+pub mod system;
+pub mod tls;
+pub mod transport;
+pub mod upstream;
 
-pub use resolver::{DnsResolver, LookupResult, LookupStatus};
+pub use cache::{CacheStatus, LookupCache};
+pub use cache_db::{CacheDb, CacheDbStats};
+pub use dnssec::DnssecStatus;
+pub use doh::{DohClient, DohMethod, DohSpec};
+pub use health::{HealthPolicy, HealthState, UpstreamHealth};
+pub use rate_limiter::RateLimiter;
+pub use resolver::{DnsResolver, LookupResult, LookupStatus, UpstreamConfig, DEFAULT_DOH_PROVIDER};
+pub use retry::{Jitter, RetryPolicy};
+pub use strategy::{Scheduler, Strategy};
+pub use system::SystemResolvers;
+pub use transport::TransportMode;
+pub use upstream::{DotSpec, Upstream, UpstreamLimits, UpstreamSpec};
@@ -0,0 +1,157 @@
+use crate::error::{Result, ReverDNSError};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Token-bucket rate limiter, shared by every lookup it throttles.
+///
+/// The bucket holds up to `burst` tokens and refills at `rate` tokens per
+/// second. Waiting callers reserve their token up front, so concurrent tasks
+/// are served in arrival order and the global rate holds regardless of how
+/// many lookups are in flight.
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Available tokens; negative while callers hold reservations
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// Create a limiter allowing `rate_per_sec` lookups per second with bursts
+    /// of up to `burst` lookups. Both are clamped to at least 1.
+    pub fn new(rate_per_sec: u32, burst: u32) -> Self {
+        let burst = f64::from(burst.max(1));
+        Self {
+            rate: f64::from(rate_per_sec.max(1)),
+            burst,
+            bucket: Mutex::new(Bucket {
+                tokens: burst,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Sustained rate in lookups per second
+    pub fn rate(&self) -> u32 {
+        self.rate as u32
+    }
+
+    /// Maximum burst size
+    pub fn burst(&self) -> u32 {
+        self.burst as u32
+    }
+
+    /// Take a token if one is available right now
+    pub fn try_acquire(&self) -> Result<()> {
+        self.reserve(Some(Duration::ZERO)).map(|_| ())
+    }
+
+    /// Wait until a token is available
+    pub async fn acquire(&self) {
+        if let Ok(wait) = self.reserve(None) {
+            if !wait.is_zero() {
+                tokio::time::sleep(wait).await;
+            }
+        }
+    }
+
+    /// Wait for a token, giving up with [`ReverDNSError::RateLimitExceeded`]
+    /// if none would be available within `max_wait`
+    pub async fn acquire_within(&self, max_wait: Duration) -> Result<()> {
+        let wait = self.reserve(Some(max_wait))?;
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        Ok(())
+    }
+
+    /// How long a caller arriving now would wait for a token, without
+    /// reserving one
+    pub fn time_until_ready(&self) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        self.refill(&mut bucket)
+    }
+
+    /// Reserve a token and return how long the caller must wait before using
+    /// it. Nothing is reserved if the wait would exceed `max_wait`.
+    fn reserve(&self, max_wait: Option<Duration>) -> Result<Duration> {
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        let wait = self.refill(&mut bucket);
+
+        if max_wait.is_some_and(|max| wait > max) {
+            return Err(ReverDNSError::RateLimitExceeded);
+        }
+
+        bucket.tokens -= 1.0;
+        Ok(wait)
+    }
+
+    /// Add the tokens accrued since the last refill and return the wait
+    /// before the next token
+    fn refill(&self, bucket: &mut Bucket) -> Duration {
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_try_acquire_respects_burst() {
+        let limiter = RateLimiter::new(1, 2);
+        assert!(limiter.try_acquire().is_ok());
+        assert!(limiter.try_acquire().is_ok());
+        assert!(limiter.time_until_ready() > Duration::ZERO);
+        assert!(matches!(
+            limiter.try_acquire(),
+            Err(ReverDNSError::RateLimitExceeded)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_acquire_within_gives_up() {
+        let limiter = RateLimiter::new(1, 1);
+        limiter.acquire().await;
+        assert!(limiter
+            .acquire_within(Duration::from_millis(10))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_global_rate_across_tasks() {
+        let limiter = Arc::new(RateLimiter::new(50, 1));
+        let start = std::time::Instant::now();
+
+        let tasks: Vec<_> = (0..10)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move { limiter.acquire().await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        // First token is immediate, the other nine are spaced 20ms apart
+        assert!(start.elapsed() >= Duration::from_millis(170));
+    }
+}
@@ -1,31 +1,125 @@
+use super::cache::{CacheStatus, LookupCache};
+use super::cache_db::CacheDb;
+use super::dnssec::DnssecStatus;
+use super::doh::{DohClient, DohMethod, DohSpec};
+use super::failure::Failure;
+use super::health::{HealthPolicy, UpstreamHealth};
+use super::rate_limiter::RateLimiter;
+use super::retry::RetryPolicy;
+use super::strategy::{Scheduler, Strategy};
+use super::system::SystemResolvers;
+use super::tls;
+use super::transport::TransportMode;
+use super::upstream::{DotSpec, Upstream, UpstreamSpec};
 use crate::error::{Result, ReverDNSError};
+use clap::ValueEnum;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
 use std::net::IpAddr;
-use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
-use tracing::debug;
+use tokio::sync::SemaphorePermit;
+use tracing::{debug, info, warn};
 use trust_dns_resolver::config::*;
-use trust_dns_resolver::TokioAsyncResolver;
+use trust_dns_resolver::proto::rr::{Name, RData, RecordType};
 
 /// DNS lookup result
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LookupResult {
     pub ip: String,
+    /// Primary name: the first PTR record
     pub hostname: Option<String>,
+    /// Every PTR name, in answer order
+    #[serde(default)]
+    pub hostnames: Vec<String>,
+    /// Whether a PTR name resolves back to `ip`; only set when forward
+    /// verification is enabled and names were found
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forward_confirmed: Option<bool>,
+    /// A and AAAA addresses of the PTR names, when verified
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forward_addresses: Vec<String>,
+    /// DNSSEC status of the PTR answer; only set when validation is enabled
+    /// and the lookup succeeded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dnssec: Option<DnssecStatus>,
+    /// Whether the result was served from the cache; only set when caching
+    /// is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<CacheStatus>,
     pub status: LookupStatus,
+    /// Lowest TTL among the PTR records, in seconds
     pub ttl: Option<u32>,
     pub latency_ms: u128,
+    /// Queries sent for the PTR record, including retries
+    #[serde(default)]
+    pub attempts: u32,
     pub resolver: String,
     pub error: Option<String>,
 }
 
+impl LookupResult {
+    /// Result for `ip` with `status` from `resolver`, after one query and
+    /// without names, TTL or error; set the rest with struct update syntax
+    pub fn new(ip: impl Into<String>, status: LookupStatus, resolver: impl Into<String>) -> Self {
+        Self {
+            ip: ip.into(),
+            hostname: None,
+            hostnames: Vec::new(),
+            forward_confirmed: None,
+            forward_addresses: Vec::new(),
+            dnssec: None,
+            cache: None,
+            status,
+            ttl: None,
+            latency_ms: 0,
+            attempts: 1,
+            resolver: resolver.into(),
+            error: None,
+        }
+    }
+
+    /// Set the PTR names, the first of which becomes `hostname`
+    pub fn with_hostnames<I, S>(mut self, hostnames: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.hostnames = hostnames.into_iter().map(Into::into).collect();
+        self.hostname = self.hostnames.first().cloned();
+        self
+    }
+}
+
 /// Status of a DNS lookup
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
 pub enum LookupStatus {
+    #[value(skip)]
     Success,
+    /// Any failure not covered by a more specific status
     Failed,
     Timeout,
     RateLimited,
+    /// The address has no reverse name (NXDOMAIN)
+    #[serde(rename = "nxdomain")]
+    #[value(name = "nxdomain")]
+    NxDomain,
+    /// The name exists but has no PTR records (NOERROR without answers)
+    #[serde(rename = "nodata")]
+    #[value(name = "nodata")]
+    NoData,
+    /// The server could not resolve the name (SERVFAIL)
+    #[serde(rename = "servfail")]
+    #[value(name = "servfail")]
+    ServFail,
+    /// The server declined to answer (REFUSED)
+    Refused,
+    /// The server could not be reached
+    NetworkError,
 }
 
 impl std::fmt::Display for LookupStatus {
@@ -35,57 +129,90 @@ impl std::fmt::Display for LookupStatus {
             LookupStatus::Failed => write!(f, "failed"),
             LookupStatus::Timeout => write!(f, "timeout"),
             LookupStatus::RateLimited => write!(f, "rate_limited"),
+            LookupStatus::NxDomain => write!(f, "nxdomain"),
+            LookupStatus::NoData => write!(f, "nodata"),
+            LookupStatus::ServFail => write!(f, "servfail"),
+            LookupStatus::Refused => write!(f, "refused"),
+            LookupStatus::NetworkError => write!(f, "network_error"),
         }
     }
 }
 
+/// DoH endpoint used when none is given
+pub const DEFAULT_DOH_PROVIDER: &str = "https://cloudflare-dns.com/dns-query";
+
+/// Upstream servers a [`DnsResolver`] is built from
+#[derive(Debug, Clone, Default)]
+pub struct UpstreamConfig {
+    /// Plain DNS servers
+    pub udp: Vec<UpstreamSpec>,
+    /// Transport for plain DNS servers that do not set their own
+    pub transport: TransportMode,
+    /// Name servers from the system configuration
+    pub system: Option<SystemResolvers>,
+    /// DNS-over-TLS servers
+    pub dot: Vec<DotSpec>,
+    /// DNS-over-HTTPS endpoints
+    pub doh: Vec<DohSpec>,
+    /// Method for DoH endpoints that do not set their own
+    pub doh_method: DohMethod,
+    /// PEM bundle to trust instead of the Mozilla roots for DoT and DoH
+    pub ca_file: Option<PathBuf>,
+    /// Also validate every PTR answer with DNSSEC
+    pub validate_dnssec: bool,
+}
+
 /// DNS Resolver wrapper
 pub struct DnsResolver {
-    resolver: TokioAsyncResolver,
+    upstreams: Vec<Upstream>,
+    scheduler: Scheduler,
     timeout: Duration,
-    retry_count: u32,
-    retry_backoff: Duration,
+    retry: RetryPolicy,
     resolver_names: String, // String representation for logging
+    rate_limiter: Option<Arc<RateLimiter>>,
+    rate_limit_wait: Option<Duration>,
+    cache: Option<Arc<LookupCache>>,
+    cache_db: Option<Arc<CacheDb>>,
+    verify_forward: bool,
+    reject_bogus: bool,
 }
 
 impl DnsResolver {
     /// Create a new DNS resolver with default settings
     pub async fn new(timeout_secs: u64, retry_count: u32, retry_backoff_ms: u64) -> Result<Self> {
-        // Use Google DNS and Cloudflare as defaults if system config fails or for consistency
-        let mut config = ResolverConfig::default();
-        config.add_name_server(NameServerConfig {
-            socket_addr: SocketAddr::new(IpAddr::V4("8.8.8.8".parse().unwrap()), 53),
-            protocol: Protocol::Udp,
-            tls_dns_name: None,
-            trust_negative_responses: true,
-            bind_addr: None,
-            tls_config: None,
-        });
-        config.add_name_server(NameServerConfig {
-            socket_addr: SocketAddr::new(IpAddr::V4("1.1.1.1".parse().unwrap()), 53),
-            protocol: Protocol::Udp,
-            tls_dns_name: None,
-            trust_negative_responses: true,
-            bind_addr: None,
-            tls_config: None,
-        });
-
-        let mut opts = ResolverOpts::default();
-        opts.timeout = Duration::from_secs(timeout_secs);
-        opts.attempts = 1; // We handle retries manually for better control
-
-        let resolver = TokioAsyncResolver::tokio(config, opts);
+        Ok(Self::with_defaults(
+            TransportMode::default(),
+            &resolver_opts(timeout_secs),
+            timeout_secs,
+            retry_count,
+            retry_backoff_ms,
+        ))
+    }
 
-        Ok(Self {
-            resolver,
-            timeout: Duration::from_secs(timeout_secs),
+    fn with_defaults(
+        transport: TransportMode,
+        opts: &ResolverOpts,
+        timeout_secs: u64,
+        retry_count: u32,
+        retry_backoff_ms: u64,
+    ) -> Self {
+        // Use Google DNS and Cloudflare as defaults if system config fails or for consistency
+        let upstreams = ["8.8.8.8", "1.1.1.1"]
+            .iter()
+            .map(|ip| Upstream::dns(&UpstreamSpec::new(ip.parse().unwrap()), transport, opts))
+            .collect();
+
+        Self::from_upstreams(
+            upstreams,
+            timeout_secs,
             retry_count,
-            retry_backoff: Duration::from_millis(retry_backoff_ms),
-            resolver_names: "default(8.8.8.8,1.1.1.1)".to_string(),
-        })
+            retry_backoff_ms,
+            "default(8.8.8.8,1.1.1.1)".to_string(),
+        )
     }
 
-    /// Create a new DNS resolver with custom resolver IPs
+    /// Create a new DNS resolver with custom resolver IPs, each optionally
+    /// carrying per-server limits (see [`UpstreamSpec`])
     pub async fn with_resolvers(
         resolver_ips: &[String],
         timeout_secs: u64,
@@ -94,177 +221,560 @@ impl DnsResolver {
         use_doh: bool,
         doh_provider: Option<String>,
     ) -> Result<Self> {
-        let mut config = ResolverConfig::new();
+        let config = UpstreamConfig {
+            udp: resolver_ips
+                .iter()
+                .map(|s| UpstreamSpec::from_str(s))
+                .collect::<Result<Vec<_>>>()?,
+            doh: if use_doh {
+                let provider = doh_provider.unwrap_or_else(|| DEFAULT_DOH_PROVIDER.to_string());
+                vec![DohSpec::from_str(&provider)?]
+            } else {
+                Vec::new()
+            },
+            ..UpstreamConfig::default()
+        };
+
+        Self::from_config(&config, timeout_secs, retry_count, retry_backoff_ms).await
+    }
+
+    /// Create a new DNS resolver from plain, DoT and DoH upstreams
+    pub async fn from_config(
+        config: &UpstreamConfig,
+        timeout_secs: u64,
+        retry_count: u32,
+        retry_backoff_ms: u64,
+    ) -> Result<Self> {
+        let mut opts = resolver_opts(timeout_secs);
+        opts.validate = config.validate_dnssec;
+        let mut upstreams = Vec::new();
         let mut names = Vec::new();
 
-        // 1. Add standard UDP resolvers
-        for ip_str in resolver_ips {
-            let ip_addr = IpAddr::from_str(ip_str)
-                .map_err(|_| ReverDNSError::InvalidResolver(ip_str.to_string()))?;
-
-            config.add_name_server(NameServerConfig {
-                socket_addr: SocketAddr::new(ip_addr, 53),
-                protocol: Protocol::Udp,
-                tls_dns_name: None,
-                trust_negative_responses: true,
-                bind_addr: None,
-                tls_config: None,
-            });
-            names.push(ip_str.clone());
+        // 1. Add plain DNS resolvers, each with its own limits and transport
+        for spec in &config.udp {
+            upstreams.push(Upstream::dns(spec, config.transport, &opts));
+            names.push(spec.to_string());
+        }
+        if let Some(system) = &config.system {
+            for server in &system.servers {
+                upstreams.push(Upstream::system(*server, system, config.transport, &opts));
+                names.push(format!("system({})", server.ip()));
+            }
         }
 
-        // 2. Add DoH resolver if requested
-        if use_doh {
-            let provider_url_str =
-                doh_provider.unwrap_or_else(|| "https://cloudflare-dns.com/dns-query".to_string());
-            names.push(format!("DoH({})", provider_url_str));
-
-            // Parse the URL
-            let url = url::Url::parse(&provider_url_str)
-                .map_err(|e| ReverDNSError::ConfigError(format!("Invalid DoH URL: {}", e)))?;
-
-            let host_str = url
-                .host_str()
-                .ok_or_else(|| ReverDNSError::ConfigError("DoH URL missing host".to_string()))?;
-            let port = url.port_or_known_default().unwrap_or(443);
-
-            // Resolve the hostname to an IP (bootstrap)
-            // We use tokio's lookup_host which uses the system resolver
-            let socket_addrs: Vec<SocketAddr> =
-                tokio::net::lookup_host(format!("{}:{}", host_str, port))
-                    .await
-                    .map_err(|e| {
-                        ReverDNSError::NetworkError(format!(
-                            "Failed to bootstrap DoH host {}: {}",
-                            host_str, e
-                        ))
-                    })?
-                    .collect();
-
-            if let Some(socket_addr) = socket_addrs.first() {
-                config.add_name_server(NameServerConfig {
-                    socket_addr: *socket_addr,
-                    protocol: Protocol::Https,
-                    tls_dns_name: Some(host_str.to_string()),
-                    trust_negative_responses: true,
-                    bind_addr: None,
-                    tls_config: None,
-                });
-                debug!("Added DoH resolver: {} ({})", host_str, socket_addr);
-            } else {
-                return Err(ReverDNSError::ConfigError(format!(
-                    "Could not resolve IP for DoH provider: {}",
-                    host_str
-                )));
+        // 2. Add DNS-over-TLS resolvers
+        if !config.dot.is_empty() {
+            let tls = tls::client_config(config.ca_file.as_deref())?;
+            for spec in &config.dot {
+                upstreams.push(Upstream::tls(spec, tls.clone(), &opts));
+                names.push(format!("DoT({})", spec));
             }
         }
 
-        // If no resolvers added (neither UDP nor DoH), fallback to default
-        if config.name_servers().is_empty() {
-            return Self::new(timeout_secs, retry_count, retry_backoff_ms).await;
+        // 3. Add DNS-over-HTTPS resolvers
+        for spec in &config.doh {
+            if spec.needs_bootstrap() {
+                warn!(
+                    "DoH host of {} is looked up through the system resolver; \
+                     pin its address as {}@IP to avoid a plaintext query",
+                    spec.url, spec.url
+                );
+            }
+            let client = DohClient::new(
+                spec,
+                config.doh_method,
+                config.ca_file.as_deref(),
+                Duration::from_secs(timeout_secs),
+            )?;
+            debug!("Added DoH resolver: {} ({})", spec, client.method());
+            upstreams.push(Upstream::https(client, &spec.limits, &opts));
+            names.push(format!("DoH({})", spec));
         }
 
-        let mut opts = ResolverOpts::default();
-        opts.timeout = Duration::from_secs(timeout_secs);
-        opts.attempts = 1;
+        // If no resolvers added (neither plain, system, DoT nor DoH), fallback
+        // to default
+        if upstreams.is_empty() {
+            return Ok(Self::with_defaults(
+                config.transport,
+                &opts,
+                timeout_secs,
+                retry_count,
+                retry_backoff_ms,
+            ));
+        }
 
-        let resolver = TokioAsyncResolver::tokio(config, opts);
+        Ok(Self::from_upstreams(
+            upstreams,
+            timeout_secs,
+            retry_count,
+            retry_backoff_ms,
+            names.join(","),
+        ))
+    }
 
-        Ok(Self {
-            resolver,
+    fn from_upstreams(
+        upstreams: Vec<Upstream>,
+        timeout_secs: u64,
+        retry_count: u32,
+        retry_backoff_ms: u64,
+        resolver_names: String,
+    ) -> Self {
+        Self {
+            scheduler: Scheduler::new(Strategy::default(), upstreams.len()),
+            upstreams,
             timeout: Duration::from_secs(timeout_secs),
-            retry_count,
-            retry_backoff: Duration::from_millis(retry_backoff_ms),
-            resolver_names: names.join(","),
-        })
+            retry: RetryPolicy::new(retry_count, Duration::from_millis(retry_backoff_ms)),
+            resolver_names,
+            rate_limiter: None,
+            rate_limit_wait: None,
+            cache: None,
+            cache_db: None,
+            verify_forward: false,
+            reject_bogus: false,
+        }
+    }
+
+    /// Throttle every query (including retries) through a shared limiter.
+    ///
+    /// With `max_wait` set, a lookup that would have to wait longer than that
+    /// for a token is reported as [`LookupStatus::RateLimited`] instead of
+    /// being sent; otherwise lookups wait as long as needed.
+    pub fn with_rate_limiter(
+        mut self,
+        limiter: Arc<RateLimiter>,
+        max_wait: Option<Duration>,
+    ) -> Self {
+        self.rate_limiter = Some(limiter);
+        self.rate_limit_wait = max_wait;
+        self
+    }
+
+    /// Serve repeated lookups of an address from `cache` while its answer's
+    /// TTL lasts
+    pub fn with_cache(mut self, cache: Arc<LookupCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Cache consulted before querying, if any
+    pub fn cache(&self) -> Option<&Arc<LookupCache>> {
+        self.cache.as_ref()
+    }
+
+    /// Reuse results stored by earlier runs in `db`, and store new ones.
+    /// It is consulted after the in-memory cache, if both are set.
+    pub fn with_cache_db(mut self, db: Arc<CacheDb>) -> Self {
+        self.cache_db = Some(db);
+        self
+    }
+
+    /// Persistent cache shared across runs, if any
+    pub fn cache_db(&self) -> Option<&Arc<CacheDb>> {
+        self.cache_db.as_ref()
+    }
+
+    /// Replace the retry policy built from the retry count and backoff
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Policy deciding which failed lookups are retried, and when
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    /// Check each PTR name with A and AAAA lookups (forward-confirmed
+    /// reverse DNS)
+    pub fn with_forward_verification(mut self, enabled: bool) -> Self {
+        self.verify_forward = enabled;
+        self
+    }
+
+    /// Report PTR answers that fail DNSSEC validation as failed lookups
+    /// rather than as successes marked `bogus`
+    pub fn with_bogus_rejection(mut self, enabled: bool) -> Self {
+        self.reject_bogus = enabled;
+        self
+    }
+
+    /// Choose how queries are spread over the upstream resolvers
+    pub fn with_strategy(mut self, strategy: Strategy) -> Self {
+        self.scheduler = Scheduler::new(strategy, self.upstreams.len());
+        self
+    }
+
+    /// Strategy used to pick an upstream for each query
+    pub fn strategy(&self) -> Strategy {
+        self.scheduler.strategy()
+    }
+
+    /// Set when unhealthy upstreams are taken out of rotation and probed
+    /// for recovery
+    pub fn with_health_policy(mut self, policy: HealthPolicy) -> Self {
+        for upstream in &mut self.upstreams {
+            upstream.set_health_policy(policy);
+        }
+        self
+    }
+
+    /// Current health of every upstream, in configuration order
+    pub fn health_report(&self) -> Vec<UpstreamHealth> {
+        self.upstreams.iter().map(|u| u.health_report()).collect()
+    }
+
+    /// Upstream resolvers, in configuration order
+    pub fn upstreams(&self) -> &[Upstream] {
+        &self.upstreams
+    }
+
+    /// Limiter shared by this resolver's lookups, if any
+    pub fn rate_limiter(&self) -> Option<&Arc<RateLimiter>> {
+        self.rate_limiter.as_ref()
+    }
+
+    /// Human-readable description of the configured upstream resolvers
+    pub fn description(&self) -> &str {
+        &self.resolver_names
     }
 
     /// Perform reverse DNS lookup for an IP address
     pub async fn lookup(&self, ip: &str) -> Result<LookupResult> {
-        let start = std::time::Instant::now();
+        self.lookup_with_timeout(ip, self.timeout).await
+    }
 
+    /// Reverse lookup for an IP address with `timeout` for each query instead
+    /// of the resolver's own. Retries are still made as the policy allows.
+    pub async fn lookup_with_timeout(&self, ip: &str, timeout: Duration) -> Result<LookupResult> {
         // Validate IP address
         let ip_addr =
             IpAddr::from_str(ip).map_err(|_| ReverDNSError::InvalidIpAddress(ip.to_string()))?;
 
+        if self.cache.is_none() && self.cache_db.is_none() {
+            return Ok(self.resolve(ip, ip_addr, timeout).await.0);
+        }
+        if let Some(mut result) = self.cached(ip_addr).await {
+            debug!("Cache hit for IP: {}", ip);
+            result.ip = ip.to_string();
+            return Ok(result);
+        }
+
+        let (mut result, negative_ttl) = self.resolve(ip, ip_addr, timeout).await;
+        if let Some(cache) = &self.cache {
+            cache.insert(ip_addr, &result, negative_ttl);
+        }
+        if let Some(db) = &self.cache_db {
+            if let Err(e) = db.insert(ip_addr, &result, negative_ttl) {
+                warn!("Cannot store {} in the cache database: {}", ip, e);
+            }
+        }
+        result.cache = Some(CacheStatus::Miss);
+        Ok(result)
+    }
+
+    /// Result for `ip_addr` from the in-memory cache or, failing that, the
+    /// cache database, read on the blocking pool and kept in memory for the
+    /// rest of its TTL. Database errors are logged and treated as a miss.
+    async fn cached(&self, ip_addr: IpAddr) -> Option<LookupResult> {
+        if let Some(result) = self.cache.as_ref().and_then(|cache| cache.get(ip_addr)) {
+            return Some(result);
+        }
+        let db = self.cache_db.clone()?;
+        let read = tokio::task::spawn_blocking(move || db.get(ip_addr))
+            .await
+            .map_err(|e| ReverDNSError::InternalError(e.to_string()))
+            .and_then(|read| read);
+        match read {
+            Ok(Some((result, remaining))) => {
+                if let Some(cache) = &self.cache {
+                    cache.insert(ip_addr, &result, Some(remaining));
+                }
+                Some(result)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                warn!("Cannot read {} from the cache database: {}", ip_addr, e);
+                None
+            }
+        }
+    }
+
+    /// Query the upstreams for the PTR records of `ip_addr`, allowing
+    /// `timeout` per query, and return the result and, for NXDOMAIN and
+    /// NODATA, the negative TTL from the SOA
+    async fn resolve(
+        &self,
+        ip: &str,
+        ip_addr: IpAddr,
+        timeout: Duration,
+    ) -> (LookupResult, Option<u32>) {
+        let start = std::time::Instant::now();
+        // Time spent waiting for the rate limiter is not part of the latency
+        let mut throttled = Duration::ZERO;
+
         debug!("Looking up IP: {}", ip);
 
         // Perform lookup with retry logic
-        let mut last_error = None;
-
-        for attempt in 0..=self.retry_count {
-            if attempt > 0 {
-                tokio::time::sleep(self.retry_backoff * attempt).await;
+        let policy = &self.retry;
+        // Server that answered (or last failed) this lookup
+        let mut last_upstream = None;
+        let mut attempts = 0;
+        let mut delay = Duration::ZERO;
+
+        let failure = loop {
+            if attempts > 0 {
+                delay = policy.backoff(attempts, delay);
                 debug!(
-                    "Retrying lookup for {} (attempt {}/{})",
-                    ip, attempt, self.retry_count
+                    "Retrying lookup for {} in {:?} (retry {}/{})",
+                    ip, delay, attempts, policy.max_retries
                 );
+                tokio::time::sleep(delay).await;
             }
+            attempts += 1;
+
+            let avoid = last_upstream.filter(|_| policy.switch_resolver);
+            let wait_start = std::time::Instant::now();
+            let admitted = self.admit(avoid).await;
+            throttled += wait_start.elapsed();
+            let (index, _permit) = match admitted {
+                Ok(admitted) => admitted,
+                Err(e) => {
+                    let failure = Failure::rate_limited(&e);
+                    if failure.retryable && policy.should_retry(failure.status, attempts) {
+                        continue;
+                    }
+                    break failure;
+                }
+            };
+
+            let upstream = &self.upstreams[index];
+            last_upstream = Some(index);
+
+            // Create reverse lookup query; a validating upstream answers
+            // with the status of the same records, and `None` is a timeout
+            let query_start = std::time::Instant::now();
+            let (result, dnssec) = match upstream.validated_reverse_lookup(ip_addr, timeout).await {
+                Some(Some(validated)) => {
+                    debug!(
+                        "DNSSEC status of {}: {} {}",
+                        ip, validated.status, validated.reason
+                    );
+                    (
+                        Some(validated.answer),
+                        Some((validated.status, validated.reason)),
+                    )
+                }
+                Some(None) => (None, None),
+                None => (
+                    tokio::time::timeout(timeout, upstream.reverse_lookup(ip_addr))
+                        .await
+                        .ok(),
+                    None,
+                ),
+            };
+
+            let failure = match result {
+                Some(Ok(lookup_result)) => {
+                    record_success(upstream, query_start.elapsed());
+                    let latency_ms = start.elapsed().saturating_sub(throttled).as_millis();
+
+                    // Keep every PTR name, without the trailing dot, and the
+                    // lowest TTL among them
+                    let mut hostnames: Vec<String> = Vec::new();
+                    let mut ttl: Option<u32> = None;
+                    for record in lookup_result.records() {
+                        if let Some(RData::PTR(ptr)) = record.data() {
+                            let name = ptr.to_utf8().trim_end_matches('.').to_string();
+                            if !hostnames.contains(&name) {
+                                hostnames.push(name);
+                            }
+                            ttl = Some(ttl.map_or(record.ttl(), |t| t.min(record.ttl())));
+                        }
+                    }
+
+                    if let (true, Some((DnssecStatus::Bogus, reason))) =
+                        (self.reject_bogus, &dnssec)
+                    {
+                        return (
+                            LookupResult {
+                                dnssec: Some(DnssecStatus::Bogus),
+                                latency_ms,
+                                attempts,
+                                error: Some(format!("DNSSEC validation failed: {}", reason)),
+                                ..LookupResult::new(ip, LookupStatus::Failed, upstream.name())
+                            },
+                            None,
+                        );
+                    }
 
-            // Create reverse lookup query
-            let result =
-                tokio::time::timeout(self.timeout, self.resolver.reverse_lookup(ip_addr)).await;
-
-            match result {
-                Ok(Ok(lookup_result)) => {
-                    let latency_ms = start.elapsed().as_millis();
-
-                    // Taking the first hostname if available
-                    let hostname = lookup_result.iter().next().map(|h| h.to_utf8());
-                    // Remove trailing dot
-                    let hostname = hostname.map(|h| h.trim_end_matches('.').to_string());
-
-                    // TTL is not directly exposed in reverse_lookup simplified result in all trust-dns versions,
-                    // but usually available if we used basic lookup. For now keeping None or 0.
-                    let ttl: Option<u32> = None;
-
-                    return Ok(LookupResult {
-                        ip: ip.to_string(),
-                        hostname,
-                        status: LookupStatus::Success,
-                        ttl,
-                        latency_ms,
-                        resolver: self.resolver_names.clone(),
-                        error: None,
-                    });
+                    let (forward_confirmed, forward_addresses) = if self.verify_forward
+                        && !hostnames.is_empty()
+                    {
+                        let addresses = self.forward_lookup(upstream, &hostnames, timeout).await;
+                        (Some(addresses.contains(&ip_addr)), addresses)
+                    } else {
+                        (None, Vec::new())
+                    };
+
+                    return (
+                        LookupResult {
+                            forward_confirmed,
+                            forward_addresses: forward_addresses
+                                .iter()
+                                .map(|a| a.to_string())
+                                .collect(),
+                            dnssec: dnssec.map(|(status, _)| status),
+                            ttl,
+                            latency_ms,
+                            attempts,
+                            ..LookupResult::new(ip, LookupStatus::Success, upstream.name())
+                                .with_hostnames(hostnames)
+                        },
+                        None,
+                    );
                 }
-                Ok(Err(e)) => {
-                    last_error = Some(e.to_string());
-                    // Should we retry or is it a hard failure (NXDOMAIN)?
-                    // Typically NXDOMAIN is hard failure. Connection refused is retryable.
-                    // Simplified: Retry everything except NXDOMAIN.
-                    let err_str = e.to_string();
-                    if err_str.contains("NXDOMAIN") || err_str.contains("NoRecordsFound") {
-                        break;
+                Some(Err(e)) => {
+                    let failure = Failure::classify(&e);
+                    debug!("Lookup for {} failed: {} ({})", ip, failure.status, e);
+                    if failure.answered {
+                        record_success(upstream, query_start.elapsed());
+                    } else {
+                        record_failure(upstream);
                     }
+                    failure
                 }
-                Err(_) => {
-                    last_error = Some("Timeout".to_string());
+                None => {
+                    record_failure(upstream);
+                    Failure::timeout()
                 }
+            };
+            if !(failure.retryable && policy.should_retry(failure.status, attempts)) {
+                break failure;
             }
-        }
-
-        let latency_ms = start.elapsed().as_millis();
-        let error_msg = last_error.unwrap_or_else(|| "Unknown error".to_string());
+        };
 
-        let status = if error_msg.contains("Timeout") {
-            LookupStatus::Timeout
+        let latency_ms = start.elapsed().saturating_sub(throttled).as_millis();
+        // A failure the policy would have retried once more means the
+        // retries ran out
+        let error_msg = if attempts > 1 && failure.retryable && policy.retries(failure.status) {
+            format!(
+                "{} after {} attempts: {}",
+                ReverDNSError::RetryLimitExceeded,
+                attempts,
+                failure.message
+            )
         } else {
-            LookupStatus::Failed
+            failure.message
         };
 
-        Ok(LookupResult {
-            ip: ip.to_string(),
-            hostname: None,
-            status,
-            ttl: None,
+        let result = LookupResult {
             latency_ms,
-            resolver: self.resolver_names.clone(),
+            attempts,
             error: Some(error_msg),
-        })
+            ..LookupResult::new(ip, failure.status, self.attribution(last_upstream))
+        };
+        (result, failure.negative_ttl)
+    }
+
+    /// A and AAAA addresses of `hostnames`, asked of the upstream that
+    /// answered the PTR query. Names that fail to resolve add no addresses.
+    async fn forward_lookup(
+        &self,
+        upstream: &Upstream,
+        hostnames: &[String],
+        timeout: Duration,
+    ) -> Vec<IpAddr> {
+        let queries = hostnames.iter().flat_map(|hostname| {
+            [RecordType::A, RecordType::AAAA].map(|record_type| async move {
+                // Names from PTR records are absolute
+                let name = Name::from_str(&format!("{}.", hostname)).ok()?;
+                self.throttle().await.ok()?;
+                let _permit = upstream.admit(self.rate_limit_wait).await.ok()?;
+                match tokio::time::timeout(timeout, upstream.lookup(name, record_type)).await {
+                    Ok(Ok(lookup)) => Some(lookup),
+                    Ok(Err(e)) => {
+                        debug!("{} lookup for {} failed: {}", record_type, hostname, e);
+                        None
+                    }
+                    Err(_) => {
+                        debug!("{} lookup for {} timed out", record_type, hostname);
+                        None
+                    }
+                }
+            })
+        });
+
+        let mut addresses = Vec::new();
+        for lookup in join_all(queries).await.into_iter().flatten() {
+            for record in lookup.records() {
+                let address = match record.data() {
+                    Some(RData::A(a)) => IpAddr::V4(a.0),
+                    Some(RData::AAAA(aaaa)) => IpAddr::V6(aaaa.0),
+                    _ => continue,
+                };
+                if !addresses.contains(&address) {
+                    addresses.push(address);
+                }
+            }
+        }
+        addresses
+    }
+
+    /// Wait for the global rate limit, pick an upstream (avoiding the one
+    /// that just failed) and wait for its own limits. The returned permit
+    /// must be held while the query is in flight.
+    async fn admit(&self, avoid: Option<usize>) -> Result<(usize, Option<SemaphorePermit<'_>>)> {
+        self.throttle().await?;
+        let index = self.scheduler.select(&self.upstreams, avoid);
+        let permit = self.upstreams[index].admit(self.rate_limit_wait).await?;
+        Ok((index, permit))
+    }
+
+    /// Resolver recorded in a result: the upstream queried last, or all of
+    /// them if the lookup never reached one
+    fn attribution(&self, upstream: Option<usize>) -> String {
+        match upstream {
+            Some(index) => self.upstreams[index].name().to_string(),
+            None => self.resolver_names.clone(),
+        }
+    }
+
+    /// Wait for the rate limiter, if one is attached
+    async fn throttle(&self) -> Result<()> {
+        match (&self.rate_limiter, self.rate_limit_wait) {
+            (Some(limiter), Some(max_wait)) => limiter.acquire_within(max_wait).await,
+            (Some(limiter), None) => {
+                limiter.acquire().await;
+                Ok(())
+            }
+            (None, _) => Ok(()),
+        }
     }
 }
 
+fn record_success(upstream: &Upstream, latency: Duration) {
+    if upstream.health().record_success(latency) {
+        info!("Resolver {} recovered", upstream.name());
+    }
+}
+
+fn record_failure(upstream: &Upstream) {
+    if upstream.health().record_failure() {
+        warn!(
+            "Resolver {} ejected after repeated failures",
+            upstream.name()
+        );
+    }
+}
+
+/// Resolver options shared by every upstream
+fn resolver_opts(timeout_secs: u64) -> ResolverOpts {
+    let mut opts = ResolverOpts::default();
+    opts.timeout = Duration::from_secs(timeout_secs);
+    opts.attempts = 1; // We handle retries manually for better control
+    opts
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -274,6 +784,12 @@ mod tests {
         assert_eq!(LookupStatus::Success.to_string(), "success");
         assert_eq!(LookupStatus::Failed.to_string(), "failed");
         assert_eq!(LookupStatus::Timeout.to_string(), "timeout");
+        assert_eq!(LookupStatus::NxDomain.to_string(), "nxdomain");
+        assert_eq!(LookupStatus::NetworkError.to_string(), "network_error");
+        assert_eq!(
+            serde_json::to_value(LookupStatus::ServFail).unwrap(),
+            "servfail"
+        );
     }
 
     #[tokio::test]
@@ -286,10 +802,66 @@ mod tests {
     async fn test_invalid_resolver_ip() {
         // This accepts string so we can test "invalid"
         let result =
-            DnsResolver::with_resolvers(&vec!["invalid".to_string()], 5, 1, 100, false, None).await;
+            DnsResolver::with_resolvers(&["invalid".to_string()], 5, 1, 100, false, None).await;
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_lookup_rate_limited() {
+        let limiter = Arc::new(RateLimiter::new(1, 1));
+        limiter.try_acquire().unwrap();
+
+        let resolver = DnsResolver::new(5, 0, 0)
+            .await
+            .unwrap()
+            .with_rate_limiter(limiter, Some(Duration::from_millis(10)));
+        let result = resolver.lookup("127.0.0.1").await.unwrap();
+        assert_eq!(result.status, LookupStatus::RateLimited);
+        assert!(result.error.is_some());
+    }
+
+    async fn resolver_for(specs: &[&str]) -> DnsResolver {
+        let specs: Vec<String> = specs.iter().map(|s| s.to_string()).collect();
+        DnsResolver::with_resolvers(&specs, 1, 0, 0, false, None)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_result_names_answering_upstream() {
+        // Loopback is answered locally, so no query leaves the host
+        let resolver = resolver_for(&["192.0.2.1", "192.0.2.2"])
+            .await
+            .with_strategy(Strategy::Failover);
+        let result = resolver.lookup("127.0.0.1").await.unwrap();
+        assert_eq!(result.status, LookupStatus::Success);
+        assert_eq!(result.resolver, "192.0.2.1:53/udp");
+        assert!(result.ttl.is_some_and(|ttl| ttl > 0));
+        assert!(resolver.upstreams()[0].latency().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_system_resolvers() {
+        let config = UpstreamConfig {
+            udp: vec![UpstreamSpec::from_str("192.0.2.1").unwrap()],
+            system: Some(
+                SystemResolvers::parse("nameserver 192.0.2.53\nnameserver 192.0.2.54\n").unwrap(),
+            ),
+            transport: TransportMode::Tcp,
+            ..UpstreamConfig::default()
+        };
+        let resolver = DnsResolver::from_config(&config, 1, 0, 0).await.unwrap();
+        let names: Vec<&str> = resolver.upstreams().iter().map(|u| u.name()).collect();
+        assert_eq!(
+            names,
+            vec!["192.0.2.1:53/tcp", "192.0.2.53:53/tcp", "192.0.2.54:53/tcp"]
+        );
+        assert_eq!(
+            resolver.description(),
+            "192.0.2.1,system(192.0.2.53),system(192.0.2.54)"
+        );
+    }
+
     #[tokio::test]
     async fn test_invalid_ip_lookup() {
         let resolver = DnsResolver::new(5, 0, 0).await.unwrap();
@@ -0,0 +1,165 @@
+use super::resolver::LookupStatus;
+use clap::ValueEnum;
+use rand::Rng;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Randomisation of retry delays, so lookups that failed together do not
+/// retry in lock-step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Jitter {
+    /// Exactly the exponential delay
+    None,
+    /// Anywhere between zero and the exponential delay
+    #[default]
+    Full,
+    /// Between the base delay and three times the previous delay
+    Decorrelated,
+}
+
+impl std::fmt::Display for Jitter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Jitter::None => write!(f, "none"),
+            Jitter::Full => write!(f, "full"),
+            Jitter::Decorrelated => write!(f, "decorrelated"),
+        }
+    }
+}
+
+/// Statuses retried unless configured otherwise: those that point at the
+/// resolver or the network rather than at the address
+pub const DEFAULT_RETRY_ON: [LookupStatus; 5] = [
+    LookupStatus::Timeout,
+    LookupStatus::NetworkError,
+    LookupStatus::ServFail,
+    LookupStatus::Refused,
+    LookupStatus::Failed,
+];
+
+/// When a failed lookup is tried again, and how long to wait first.
+///
+/// Retry `n` (starting at 1) waits `base * 2^(n-1)`, capped at
+/// `max_backoff`, with `jitter` applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt
+    pub max_retries: u32,
+    pub base: Duration,
+    pub max_backoff: Duration,
+    pub jitter: Jitter,
+    /// Statuses worth another attempt
+    pub retry_on: Vec<LookupStatus>,
+    /// Send retries to a different resolver when one is available
+    pub switch_resolver: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(3, Duration::from_millis(100))
+    }
+}
+
+impl RetryPolicy {
+    /// `max_retries` retries starting at `base`, with the remaining settings
+    /// at their defaults
+    pub fn new(max_retries: u32, base: Duration) -> Self {
+        Self {
+            max_retries,
+            base,
+            max_backoff: Duration::from_secs(5),
+            jitter: Jitter::default(),
+            retry_on: DEFAULT_RETRY_ON.to_vec(),
+            switch_resolver: true,
+        }
+    }
+
+    /// Whether failures with `status` are retried at all
+    pub fn retries(&self, status: LookupStatus) -> bool {
+        self.retry_on.contains(&status)
+    }
+
+    /// Whether a lookup that ended with `status` after `attempts` attempts
+    /// gets another one
+    pub fn should_retry(&self, status: LookupStatus, attempts: u32) -> bool {
+        attempts <= self.max_retries && self.retries(status)
+    }
+
+    /// Delay before retry number `retry` (starting at 1); `previous` is the
+    /// delay before the last retry, or zero
+    pub fn backoff(&self, retry: u32, previous: Duration) -> Duration {
+        let exponential = self
+            .base
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.max_backoff);
+        let delay = match self.jitter {
+            Jitter::None => exponential,
+            Jitter::Full => random_between(Duration::ZERO, exponential),
+            Jitter::Decorrelated => {
+                random_between(self.base, previous.max(self.base).saturating_mul(3))
+            }
+        };
+        delay.min(self.max_backoff)
+    }
+}
+
+fn random_between(low: Duration, high: Duration) -> Duration {
+    if high <= low {
+        return low;
+    }
+    rand::thread_rng().gen_range(low..=high)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exponential_backoff() {
+        let policy = RetryPolicy {
+            jitter: Jitter::None,
+            max_backoff: Duration::from_millis(500),
+            ..RetryPolicy::new(10, Duration::from_millis(100))
+        };
+        let delays: Vec<u128> = (1..=5)
+            .map(|n| policy.backoff(n, Duration::ZERO).as_millis())
+            .collect();
+        assert_eq!(delays, vec![100, 200, 400, 500, 500]);
+        assert_eq!(policy.backoff(u32::MAX, Duration::ZERO).as_millis(), 500);
+    }
+
+    #[test]
+    fn test_jitter_bounds() {
+        let full = RetryPolicy::new(10, Duration::from_millis(100));
+        let decorrelated = RetryPolicy {
+            jitter: Jitter::Decorrelated,
+            ..full.clone()
+        };
+        for _ in 0..100 {
+            assert!(full.backoff(3, Duration::ZERO) <= Duration::from_millis(400));
+
+            let delay = decorrelated.backoff(2, Duration::from_millis(300));
+            assert!(delay >= Duration::from_millis(100));
+            assert!(delay <= Duration::from_millis(900));
+            assert!(decorrelated.backoff(9, Duration::from_secs(60)) <= full.max_backoff);
+        }
+    }
+
+    #[test]
+    fn test_should_retry() {
+        let policy = RetryPolicy::new(2, Duration::from_millis(100));
+        assert!(policy.should_retry(LookupStatus::Timeout, 1));
+        assert!(policy.should_retry(LookupStatus::ServFail, 2));
+        assert!(!policy.should_retry(LookupStatus::ServFail, 3));
+        assert!(!policy.should_retry(LookupStatus::NxDomain, 1));
+        assert!(!policy.should_retry(LookupStatus::RateLimited, 1));
+
+        let policy = RetryPolicy {
+            retry_on: vec![LookupStatus::NxDomain],
+            ..policy
+        };
+        assert!(policy.should_retry(LookupStatus::NxDomain, 1));
+        assert!(!policy.should_retry(LookupStatus::Timeout, 1));
+    }
+}
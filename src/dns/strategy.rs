@@ -0,0 +1,250 @@
+use super::upstream::Upstream;
+use clap::ValueEnum;
+use rand::Rng;
+use serde::Deserialize;
+use std::sync::Mutex;
+
+/// How queries are spread over the upstream resolvers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    /// Each resolver in turn
+    RoundRobin,
+    /// A uniformly random resolver
+    Random,
+    /// In proportion to each resolver's weight
+    #[default]
+    Weighted,
+    /// The resolver with the lowest recent latency
+    LeastLatency,
+    /// The first resolver listed; later ones only take over when earlier
+    /// ones are at their limits or failing
+    Failover,
+}
+
+impl std::fmt::Display for Strategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Strategy::RoundRobin => write!(f, "round-robin"),
+            Strategy::Random => write!(f, "random"),
+            Strategy::Weighted => write!(f, "weighted"),
+            Strategy::LeastLatency => write!(f, "least-latency"),
+            Strategy::Failover => write!(f, "failover"),
+        }
+    }
+}
+
+/// Picks the upstream for each query according to a [`Strategy`]
+#[derive(Debug)]
+pub struct Scheduler {
+    strategy: Strategy,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    /// Next position for round-robin
+    cursor: usize,
+    /// Smooth weighted round-robin counters, one per upstream
+    current: Vec<i64>,
+}
+
+impl Scheduler {
+    pub fn new(strategy: Strategy, upstreams: usize) -> Self {
+        Self {
+            strategy,
+            state: Mutex::new(State {
+                cursor: 0,
+                current: vec![0; upstreams],
+            }),
+        }
+    }
+
+    pub fn strategy(&self) -> Strategy {
+        self.strategy
+    }
+
+    /// Index of the upstream for the next query.
+    ///
+    /// Ejected upstreams are skipped (apart from recovery probes), as are
+    /// upstreams at their rate or in-flight limit and `avoid` (the server that
+    /// just failed this lookup), while any other server is available. When
+    /// none is, the one that frees up soonest is used.
+    pub fn select(&self, upstreams: &[Upstream], avoid: Option<usize>) -> usize {
+        if upstreams.len() <= 1 {
+            // A lone server is used regardless, but once its cool-down has
+            // passed the query still serves as its probe
+            if let Some(upstream) = upstreams.first() {
+                upstream.health().try_probe();
+            }
+            return 0;
+        }
+
+        // An ejected server whose cool-down has passed gets one probe query
+        if let Some(i) =
+            (0..upstreams.len()).find(|&i| Some(i) != avoid && upstreams[i].health().try_probe())
+        {
+            return i;
+        }
+
+        // Ejected servers are only used when every server is ejected
+        let mut pool: Vec<usize> = (0..upstreams.len())
+            .filter(|&i| upstreams[i].health().is_healthy())
+            .collect();
+        if pool.is_empty() {
+            pool = (0..upstreams.len()).collect();
+        }
+
+        let candidates: Vec<usize> = pool
+            .iter()
+            .copied()
+            .filter(|&i| Some(i) != avoid && !upstreams[i].is_saturated())
+            .collect();
+        if candidates.is_empty() {
+            return pool
+                .into_iter()
+                .min_by_key(|&i| (Some(i) == avoid, upstreams[i].time_until_ready()))
+                .unwrap_or(0);
+        }
+
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match self.strategy {
+            Strategy::RoundRobin => {
+                let n = upstreams.len();
+                let pick = (0..n)
+                    .map(|offset| (state.cursor + offset) % n)
+                    .find(|i| candidates.contains(i))
+                    .unwrap_or(candidates[0]);
+                state.cursor = (pick + 1) % n;
+                pick
+            }
+            Strategy::Random => candidates[rand::thread_rng().gen_range(0..candidates.len())],
+            Strategy::Weighted => {
+                let total: i64 = candidates
+                    .iter()
+                    .map(|&i| i64::from(upstreams[i].weight()))
+                    .sum();
+                let mut best = candidates[0];
+                for &i in &candidates {
+                    state.current[i] += i64::from(upstreams[i].weight());
+                    if state.current[i] > state.current[best] {
+                        best = i;
+                    }
+                }
+                state.current[best] -= total;
+                best
+            }
+            // Servers without a measurement yet sort first, so each is tried
+            Strategy::LeastLatency => candidates
+                .iter()
+                .copied()
+                .min_by_key(|&i| upstreams[i].latency())
+                .unwrap_or(candidates[0]),
+            Strategy::Failover => candidates[0],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::{HealthPolicy, UpstreamSpec};
+    use std::str::FromStr;
+    use std::time::Duration;
+    use trust_dns_resolver::config::ResolverOpts;
+
+    fn upstreams(specs: &[&str]) -> Vec<Upstream> {
+        specs
+            .iter()
+            .map(|s| {
+                Upstream::udp(
+                    &UpstreamSpec::from_str(s).unwrap(),
+                    &ResolverOpts::default(),
+                )
+            })
+            .collect()
+    }
+
+    fn picks(strategy: Strategy, upstreams: &[Upstream], n: usize) -> Vec<usize> {
+        let scheduler = Scheduler::new(strategy, upstreams.len());
+        (0..n).map(|_| scheduler.select(upstreams, None)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_round_robin_and_failover() {
+        let ups = upstreams(&["192.0.2.1", "192.0.2.2", "192.0.2.3"]);
+        assert_eq!(picks(Strategy::RoundRobin, &ups, 4), vec![0, 1, 2, 0]);
+        assert_eq!(picks(Strategy::Failover, &ups, 3), vec![0, 0, 0]);
+
+        let scheduler = Scheduler::new(Strategy::Failover, ups.len());
+        assert_eq!(scheduler.select(&ups, Some(0)), 1);
+    }
+
+    #[tokio::test]
+    async fn test_weighted() {
+        let ups = upstreams(&["192.0.2.1@weight=3", "192.0.2.2"]);
+        let picks = picks(Strategy::Weighted, &ups, 8);
+        assert_eq!(picks.iter().filter(|&&i| i == 0).count(), 6);
+        // Smooth round-robin interleaves rather than sending runs of three
+        assert_eq!(&picks[..4], &[0, 0, 1, 0]);
+    }
+
+    #[tokio::test]
+    async fn test_random_stays_in_range() {
+        let ups = upstreams(&["192.0.2.1", "192.0.2.2"]);
+        assert!(picks(Strategy::Random, &ups, 50).iter().all(|&i| i < 2));
+    }
+
+    #[tokio::test]
+    async fn test_least_latency() {
+        let ups = upstreams(&["192.0.2.1", "192.0.2.2"]);
+        ups[0].health().record_success(Duration::from_millis(80));
+        // The unmeasured server is tried first, then the faster one wins
+        assert_eq!(picks(Strategy::LeastLatency, &ups, 1), vec![1]);
+        ups[1].health().record_success(Duration::from_millis(20));
+        assert_eq!(picks(Strategy::LeastLatency, &ups, 2), vec![1, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_skips_saturated_upstream() {
+        let ups = upstreams(&["192.0.2.1@1qps", "192.0.2.2@1qps"]);
+        let scheduler = Scheduler::new(Strategy::Failover, ups.len());
+        ups[0].admit(None).await.unwrap();
+        assert_eq!(scheduler.select(&ups, None), 1);
+        ups[1].admit(None).await.unwrap();
+        assert!(ups.iter().all(|u| u.is_saturated()));
+    }
+
+    #[tokio::test]
+    async fn test_skips_ejected_upstream() {
+        let ups = upstreams(&["192.0.2.1", "192.0.2.2"]);
+        let scheduler = Scheduler::new(Strategy::RoundRobin, ups.len());
+        while ups[0].health().is_healthy() {
+            ups[0].health().record_failure();
+        }
+        assert_eq!(picks(Strategy::RoundRobin, &ups, 3), vec![1, 1, 1]);
+
+        // With every server ejected, traffic still flows
+        while ups[1].health().is_healthy() {
+            ups[1].health().record_failure();
+        }
+        assert_eq!(scheduler.select(&ups, Some(0)), 1);
+    }
+
+    #[tokio::test]
+    async fn test_lone_upstream_is_probed() {
+        let mut ups = upstreams(&["192.0.2.1"]);
+        ups[0].set_health_policy(HealthPolicy {
+            failure_threshold: 1,
+            cooldown: Duration::ZERO,
+            max_cooldown: Duration::ZERO,
+        });
+        ups[0].health().record_failure();
+        assert!(!ups[0].health().is_healthy());
+
+        let scheduler = Scheduler::new(Strategy::RoundRobin, ups.len());
+        assert_eq!(scheduler.select(&ups, None), 0);
+        assert!(ups[0].health().record_success(Duration::from_millis(5)));
+        assert!(ups[0].health().is_healthy());
+    }
+}
@@ -0,0 +1,188 @@
+use crate::error::{Result, ReverDNSError};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+use trust_dns_resolver::config::{ResolverConfig, ResolverOpts};
+use trust_dns_resolver::proto::rr::Name;
+use trust_dns_resolver::system_conf::parse_resolv_conf;
+
+/// Location of the host's resolver configuration
+pub const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+/// Name servers and options of the host's own resolver, as read from
+/// `resolv.conf`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemResolvers {
+    /// `nameserver` entries, in file order
+    pub servers: Vec<SocketAddr>,
+    /// `domain` entry, or the domain of the host name
+    pub domain: Option<Name>,
+    /// `search` list, or the domain when there is none
+    pub search: Vec<Name>,
+    /// `options timeout:N`, if given
+    pub timeout: Option<Duration>,
+    /// `options attempts:N`, if given
+    pub attempts: Option<usize>,
+    /// `options ndots:N`
+    pub ndots: usize,
+}
+
+impl SystemResolvers {
+    /// Read [`RESOLV_CONF`]
+    pub fn load() -> Result<Self> {
+        Self::from_file(Path::new(RESOLV_CONF))
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            ReverDNSError::ConfigError(format!("Cannot read {}: {}", path.display(), e))
+        })?;
+        Self::parse(&content)
+    }
+
+    /// Parse `resolv.conf` content; at least one name server is required
+    pub fn parse(content: &str) -> Result<Self> {
+        let invalid = |e: &dyn std::fmt::Display| {
+            ReverDNSError::ConfigError(format!("Invalid resolv.conf: {}", e))
+        };
+        let (config, opts) = parse_resolv_conf(content).map_err(|e| invalid(&e))?;
+
+        // Each server is listed once per protocol
+        let mut servers = Vec::new();
+        for server in config.name_servers() {
+            if !servers.contains(&server.socket_addr) {
+                servers.push(server.socket_addr);
+            }
+        }
+        if servers.is_empty() {
+            return Err(invalid(&"no nameserver entries"));
+        }
+
+        // The parser fills in defaults for missing options; only those set
+        // in the file are kept
+        let has_option = |name: &str| {
+            content
+                .lines()
+                .filter_map(|line| line.trim().strip_prefix("options"))
+                .flat_map(str::split_whitespace)
+                .any(|option| option.starts_with(name))
+        };
+
+        Ok(Self {
+            servers,
+            domain: config.domain().cloned(),
+            search: config.search().to_vec(),
+            timeout: Some(opts.timeout).filter(|_| has_option("timeout:")),
+            attempts: Some(opts.attempts).filter(|_| has_option("attempts:")),
+            ndots: opts.ndots,
+        })
+    }
+
+    /// Search domains and the local domain, without any servers
+    pub(crate) fn resolver_config(&self) -> ResolverConfig {
+        ResolverConfig::from_parts(self.domain.clone(), self.search.clone(), vec![])
+    }
+
+    /// `opts` with this configuration's ndots. The timeout and attempts are
+    /// left alone, since each lookup sends a single query per try and
+    /// retries through its retry policy; they are mapped onto that instead
+    /// (see [`crate::config::apply_system_resolvers`])
+    pub(crate) fn resolver_opts(&self, opts: &ResolverOpts) -> ResolverOpts {
+        let mut opts = *opts;
+        opts.ndots = self.ndots;
+        opts
+    }
+
+    /// Lookup timeout in whole seconds, if `options timeout:N` is given
+    pub fn timeout_secs(&self) -> Option<u64> {
+        self.timeout.map(|t| t.as_secs().max(1))
+    }
+
+    /// Retries after the first query, if `options attempts:N` is given
+    pub fn retries(&self) -> Option<u32> {
+        self.attempts
+            .map(|a| u32::try_from(a.saturating_sub(1)).unwrap_or(u32::MAX))
+    }
+}
+
+impl std::fmt::Display for SystemResolvers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let servers: Vec<String> = self.servers.iter().map(|s| s.ip().to_string()).collect();
+        write!(f, "{}", servers.join(", "))?;
+        if !self.search.is_empty() {
+            let search: Vec<String> = self
+                .search
+                .iter()
+                .map(|n| n.to_string().trim_end_matches('.').to_string())
+                .collect();
+            write!(f, "; search {}", search.join(" "))?;
+        }
+        if let Some(timeout) = self.timeout {
+            write!(f, "; timeout {}s", timeout.as_secs())?;
+        }
+        if let Some(attempts) = self.attempts {
+            write!(f, "; attempts {}", attempts)?;
+        }
+        write!(f, "; ndots {}", self.ndots)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_resolv_conf() {
+        let system = SystemResolvers::parse(
+            "# generated\n\
+             nameserver 10.0.0.2\n\
+             nameserver 2001:db8::53\n\
+             search corp.example lab.example\n\
+             options timeout:2 attempts:3 ndots:2\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            system.servers,
+            vec![
+                "10.0.0.2:53".parse().unwrap(),
+                "[2001:db8::53]:53".parse().unwrap()
+            ]
+        );
+        assert_eq!(system.search.len(), 2);
+        assert_eq!(system.timeout, Some(Duration::from_secs(2)));
+        assert_eq!(system.attempts, Some(3));
+        assert_eq!(system.ndots, 2);
+        assert_eq!(system.timeout_secs(), Some(2));
+        assert_eq!(system.retries(), Some(2));
+        assert_eq!(
+            system.to_string(),
+            "10.0.0.2, 2001:db8::53; search corp.example lab.example; \
+             timeout 2s; attempts 3; ndots 2"
+        );
+
+        // Retries stay with the retry policy, not the resolver library
+        let mut defaults = ResolverOpts::default();
+        defaults.attempts = 1;
+        let opts = system.resolver_opts(&defaults);
+        assert_eq!(opts.ndots, 2);
+        assert_eq!(opts.attempts, 1);
+        assert_eq!(opts.timeout, defaults.timeout);
+        assert_eq!(system.resolver_config().search().len(), 2);
+    }
+
+    #[test]
+    fn test_parse_resolv_conf_without_options() {
+        let system = SystemResolvers::parse("nameserver 10.0.0.2\noptions ndots:3\n").unwrap();
+        assert_eq!(system.timeout, None);
+        assert_eq!(system.attempts, None);
+        assert_eq!(system.retries(), None);
+        assert_eq!(system.to_string(), "10.0.0.2; ndots 3");
+    }
+
+    #[test]
+    fn test_parse_resolv_conf_without_servers() {
+        assert!(SystemResolvers::parse("search corp.example\n").is_err());
+        assert!(SystemResolvers::from_file(Path::new("/nonexistent/resolv.conf")).is_err());
+    }
+}
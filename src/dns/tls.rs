@@ -0,0 +1,68 @@
+use crate::error::{Result, ReverDNSError};
+use rustls::{ClientConfig, OwnedTrustAnchor, RootCertStore};
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+
+/// TLS settings shared by the DoT and DoH upstreams.
+///
+/// Certificates are checked against the Mozilla root store, or only against
+/// the certificates in `ca_file` when one is given. Unlike the trust-dns
+/// default, SNI is sent so servers hosting several names present the right
+/// certificate.
+pub fn client_config(ca_file: Option<&Path>) -> Result<Arc<ClientConfig>> {
+    let mut roots = RootCertStore::empty();
+    match ca_file {
+        Some(path) => {
+            let certs = load_certificates(path)?;
+            let (_, invalid) = roots.add_parsable_certificates(&certs);
+            if roots.is_empty() {
+                return Err(ReverDNSError::ConfigError(format!(
+                    "No usable CA certificates in {} ({} invalid)",
+                    path.display(),
+                    invalid
+                )));
+            }
+        }
+        None => {
+            roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+                OwnedTrustAnchor::from_subject_spki_name_constraints(
+                    ta.subject,
+                    ta.spki,
+                    ta.name_constraints,
+                )
+            }));
+        }
+    }
+
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+/// DER certificates from a PEM bundle
+pub(crate) fn load_certificates(path: &Path) -> Result<Vec<Vec<u8>>> {
+    let file = std::fs::File::open(path).map_err(|e| {
+        ReverDNSError::ConfigError(format!("Cannot read CA file {}: {}", path.display(), e))
+    })?;
+    rustls_pemfile::certs(&mut BufReader::new(file)).map_err(|e| {
+        ReverDNSError::ConfigError(format!("Invalid CA file {}: {}", path.display(), e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ca_file() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tls");
+        assert!(client_config(Some(&dir.join("ca.pem"))).is_ok());
+        // A private key holds no certificates
+        assert!(client_config(Some(&dir.join("server.key"))).is_err());
+        assert!(client_config(Some(&dir.join("missing.pem"))).is_err());
+        assert!(client_config(None).unwrap().enable_sni);
+    }
+}
@@ -0,0 +1,216 @@
+use clap::ValueEnum;
+use futures::{stream, Future, Stream, StreamExt};
+use serde::Deserialize;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::debug;
+use trust_dns_resolver::config::{NameServerConfig, Protocol, ResolverOpts};
+use trust_dns_resolver::error::{ResolveError, ResolveErrorKind};
+use trust_dns_resolver::name_server::{
+    ConnectionProvider, GenericConnection, TokioConnectionProvider, TokioRuntimeProvider,
+};
+use trust_dns_resolver::proto::error::{ProtoError, ProtoErrorKind};
+use trust_dns_resolver::proto::xfer::{DnsHandle, DnsRequest, DnsResponse};
+
+/// Error reported for a UDP response with the TC bit set
+const TRUNCATED: &str = "response truncated over UDP; use --transport udp-then-tcp or tcp";
+
+/// How plain DNS queries reach a server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TransportMode {
+    /// UDP only; truncated responses are reported as failures
+    #[default]
+    Udp,
+    /// TCP only, for networks that drop UDP/53
+    Tcp,
+    /// UDP, repeating the query over TCP when the response is truncated or
+    /// the UDP query fails
+    UdpThenTcp,
+}
+
+impl TransportMode {
+    /// Protocols queried, in order
+    pub(crate) fn protocols(&self) -> &'static [Protocol] {
+        match self {
+            TransportMode::Udp => &[Protocol::Udp],
+            TransportMode::Tcp => &[Protocol::Tcp],
+            TransportMode::UdpThenTcp => &[Protocol::Udp, Protocol::Tcp],
+        }
+    }
+
+    /// Suffix of upstream names using this mode
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            TransportMode::Udp => "udp",
+            TransportMode::Tcp => "tcp",
+            TransportMode::UdpThenTcp => "udp+tcp",
+        }
+    }
+}
+
+impl std::fmt::Display for TransportMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransportMode::Udp => write!(f, "udp"),
+            TransportMode::Tcp => write!(f, "tcp"),
+            TransportMode::UdpThenTcp => write!(f, "udp-then-tcp"),
+        }
+    }
+}
+
+/// Error for a truncated UDP response. A protocol error, so the name server
+/// pool reports it in preference to its generic "no connections" error.
+fn truncated() -> ResolveError {
+    ProtoError::from(ProtoErrorKind::Msg(TRUNCATED.to_string())).into()
+}
+
+/// Whether `error` is a truncated UDP response
+pub(crate) fn is_truncated(error: &ResolveError) -> bool {
+    matches!(
+        error.kind(),
+        ResolveErrorKind::Proto(e) if matches!(e.kind(), ProtoErrorKind::Msg(msg) if msg == TRUNCATED)
+    )
+}
+
+/// Connection provider that turns truncated UDP responses into errors.
+///
+/// trust-dns hands a truncated response back as a normal answer when no TCP
+/// server is configured, silently dropping the records that did not fit.
+/// Failing instead makes the truncation visible, and with a TCP server
+/// configured the name server pool repeats the query over TCP.
+#[derive(Clone, Default)]
+pub(crate) struct TruncationCheck(TokioConnectionProvider);
+
+impl ConnectionProvider for TruncationCheck {
+    type Conn = CheckedConnection;
+    type FutureConn = Pin<Box<dyn Future<Output = Result<Self::Conn, ResolveError>> + Send>>;
+    type RuntimeProvider = TokioRuntimeProvider;
+
+    fn new_connection(
+        &self,
+        config: &NameServerConfig,
+        options: &ResolverOpts,
+    ) -> Self::FutureConn {
+        let udp = config.protocol == Protocol::Udp;
+        let connection = self.0.new_connection(config, options);
+        Box::pin(async move {
+            Ok(CheckedConnection {
+                inner: connection.await?,
+                udp,
+            })
+        })
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct CheckedConnection {
+    inner: GenericConnection,
+    udp: bool,
+}
+
+impl DnsHandle for CheckedConnection {
+    type Response = Pin<Box<dyn Stream<Item = Result<DnsResponse, ResolveError>> + Send>>;
+    type Error = ResolveError;
+
+    fn send<R: Into<DnsRequest> + Unpin + Send + 'static>(&mut self, request: R) -> Self::Response {
+        let udp = self.udp;
+        Box::pin(
+            self.inner
+                .send(request)
+                .map(move |response| match response {
+                    Ok(response) if udp && response.truncated() => {
+                        debug!("Truncated UDP response: {:?}", response.queries());
+                        Err(truncated())
+                    }
+                    response => response,
+                }),
+        )
+    }
+}
+
+/// Connections to one server, tried in order, that hand every response back
+/// as received.
+///
+/// The name server pool turns NXDOMAIN and NODATA responses into errors,
+/// dropping the NSEC records that prove them; the DNSSEC validator needs
+/// those records to authenticate a denial. A connection that fails is
+/// opened again for the next query.
+#[derive(Clone)]
+pub(crate) struct Exchange {
+    servers: Arc<[NameServerConfig]>,
+    connections: Arc<[Mutex<Option<CheckedConnection>>]>,
+    options: ResolverOpts,
+    provider: TruncationCheck,
+}
+
+impl Exchange {
+    pub(crate) fn new(servers: Vec<NameServerConfig>, options: &ResolverOpts) -> Self {
+        Self {
+            connections: servers.iter().map(|_| Mutex::new(None)).collect(),
+            servers: servers.into(),
+            options: *options,
+            provider: TruncationCheck::default(),
+        }
+    }
+
+    async fn exchange(self, request: DnsRequest) -> Result<DnsResponse, ResolveError> {
+        let mut error = ResolveError::from(ResolveErrorKind::NoConnections);
+        for (server, slot) in self.servers.iter().zip(self.connections.iter()) {
+            let mut connection = {
+                let mut slot = slot.lock().await;
+                match &*slot {
+                    Some(connection) => connection.clone(),
+                    None => match self.provider.new_connection(server, &self.options).await {
+                        Ok(connection) => slot.insert(connection).clone(),
+                        Err(e) => {
+                            error = e;
+                            continue;
+                        }
+                    },
+                }
+            };
+            match connection.send(request.clone()).next().await {
+                Some(Ok(response)) => return Ok(response),
+                Some(Err(e)) => error = e,
+                None => error = ResolveError::from("no response"),
+            }
+            if !is_truncated(&error) {
+                *slot.lock().await = None;
+            }
+        }
+        Err(error)
+    }
+}
+
+impl DnsHandle for Exchange {
+    type Response = Pin<Box<dyn Stream<Item = Result<DnsResponse, ResolveError>> + Send>>;
+    type Error = ResolveError;
+
+    fn send<R: Into<DnsRequest> + Unpin + Send + 'static>(&mut self, request: R) -> Self::Response {
+        Box::pin(stream::once(self.clone().exchange(request.into())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transport_mode() {
+        assert_eq!(TransportMode::default(), TransportMode::Udp);
+        assert_eq!(
+            TransportMode::from_str("udp-then-tcp", true),
+            Ok(TransportMode::UdpThenTcp)
+        );
+        assert_eq!(TransportMode::UdpThenTcp.to_string(), "udp-then-tcp");
+        assert_eq!(TransportMode::UdpThenTcp.protocols().len(), 2);
+
+        assert!(is_truncated(&truncated()));
+        assert!(!is_truncated(
+            &ResolveErrorKind::Msg(TRUNCATED.to_string()).into()
+        ));
+        assert!(!is_truncated(&ResolveErrorKind::Timeout.into()));
+    }
+}
@@ -0,0 +1,733 @@
+use super::dnssec::{self, Validated};
+use super::doh::DohClient;
+use super::health::{Health, HealthPolicy, UpstreamHealth};
+use super::rate_limiter::RateLimiter;
+use super::system::SystemResolvers;
+use super::transport::{Exchange, TransportMode, TruncationCheck};
+use crate::error::{Result, ReverDNSError};
+use clap::ValueEnum;
+use rustls::ClientConfig;
+use serde::{Deserialize, Deserializer};
+use std::net::{IpAddr, SocketAddr};
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Semaphore, SemaphorePermit};
+use trust_dns_resolver::config::{
+    NameServerConfig, Protocol, ResolverConfig, ResolverOpts, TlsClientConfig,
+};
+use trust_dns_resolver::error::ResolveError;
+use trust_dns_resolver::lookup::Lookup;
+use trust_dns_resolver::proto::op::Query;
+use trust_dns_resolver::proto::rr::{Name, RecordType};
+use trust_dns_resolver::AsyncResolver;
+
+/// Upstream resolver address with optional per-server limits.
+///
+/// Written on the command line as `ADDR[:PORT][@OPT[/OPT...]]`, with IPv6
+/// addresses in brackets when a port is given, and where each option is
+/// one of `<N>qps` (or `qps=<N>`), `max=<N>`, `weight=<N>` and
+/// `transport=udp|tcp|udp-then-tcp`:
+///
+/// ```text
+/// 8.8.8.8@50qps/max=20
+/// 10.0.0.53@5000qps/weight=10/transport=tcp
+/// 127.0.0.1:5353
+/// [2001:db8::1]:5300@transport=udp-then-tcp
+/// ```
+///
+/// or in `.reverdns.toml` as a `[[resolvers]]` table with the keys `address`,
+/// `rate_limit`, `max_in_flight`, `weight` and `transport`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpstreamSpec {
+    /// Server address; the port defaults to 53
+    #[serde(deserialize_with = "deserialize_server")]
+    pub address: SocketAddr,
+    /// Queries per second sent to this server
+    #[serde(default)]
+    pub rate_limit: Option<NonZeroU32>,
+    /// Queries in flight to this server at any time
+    #[serde(default)]
+    pub max_in_flight: Option<NonZeroUsize>,
+    /// Relative share of queries sent to this server
+    #[serde(default = "default_weight")]
+    pub weight: NonZeroU32,
+    /// Overrides the default transport when set
+    #[serde(default)]
+    pub transport: Option<TransportMode>,
+}
+
+fn default_weight() -> NonZeroU32 {
+    NonZeroU32::MIN
+}
+
+fn deserialize_server<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<SocketAddr, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    parse_server(&s, DNS_PORT)
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid resolver address: {}", s)))
+}
+
+/// Standard DNS port
+pub const DNS_PORT: u16 = 53;
+
+/// Parse `IP`, `IP:PORT`, `[IPV6]` or `[IPV6]:PORT`, using `default_port`
+/// when none is given
+pub(crate) fn parse_server(s: &str, default_port: u16) -> Option<SocketAddr> {
+    let s = s.trim();
+    if let Ok(server) = SocketAddr::from_str(s) {
+        return Some(server);
+    }
+    let ip = match s.strip_prefix('[') {
+        Some(rest) => rest.strip_suffix(']')?,
+        None => s,
+    };
+    IpAddr::from_str(ip)
+        .ok()
+        .map(|ip| SocketAddr::new(ip, default_port))
+}
+
+impl UpstreamSpec {
+    /// Spec for a server on port 53 without limits
+    pub fn new(address: IpAddr) -> Self {
+        Self::with_limits(
+            SocketAddr::new(address, DNS_PORT),
+            UpstreamLimits::default(),
+        )
+    }
+
+    pub fn with_limits(address: SocketAddr, limits: UpstreamLimits) -> Self {
+        Self {
+            address,
+            rate_limit: limits.rate_limit,
+            max_in_flight: limits.max_in_flight,
+            weight: limits.weight,
+            transport: None,
+        }
+    }
+
+    /// This server's limits
+    pub fn limits(&self) -> UpstreamLimits {
+        UpstreamLimits {
+            rate_limit: self.rate_limit,
+            max_in_flight: self.max_in_flight,
+            weight: self.weight,
+        }
+    }
+}
+
+impl FromStr for UpstreamSpec {
+    type Err = ReverDNSError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let invalid = || ReverDNSError::InvalidResolver(s.to_string());
+
+        let (address, options) = match s.split_once('@') {
+            Some((address, options)) => (address, Some(options)),
+            None => (s, None),
+        };
+        let address = parse_server(address, DNS_PORT).ok_or_else(invalid)?;
+        let mut limits = UpstreamLimits::default();
+        let mut transport = None;
+        for option in options.into_iter().flat_map(|o| o.split('/')) {
+            if let Some(mode) = option.trim().strip_prefix("transport=") {
+                transport =
+                    Some(TransportMode::from_str(mode.trim(), true).map_err(|_| invalid())?);
+            } else {
+                limits.set_option(option).ok_or_else(invalid)?;
+            }
+        }
+        Ok(Self {
+            transport,
+            ..Self::with_limits(address, limits)
+        })
+    }
+}
+
+impl std::fmt::Display for UpstreamSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The port is only written when it is not the default
+        if self.address.port() == DNS_PORT {
+            write!(f, "{}", self.address.ip())?;
+        } else {
+            write!(f, "{}", self.address)?;
+        }
+        let mut options = self.limits().options();
+        if let Some(transport) = self.transport {
+            options.push(format!("transport={}", transport));
+        }
+        write_options(f, options)
+    }
+}
+
+/// Per-server limits, written after a resolver's `@` as `/`-separated
+/// options: `<N>qps` (or `qps=<N>`), `max=<N>` and `weight=<N>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpstreamLimits {
+    /// Queries per second sent to this server
+    pub rate_limit: Option<NonZeroU32>,
+    /// Queries in flight to this server at any time
+    pub max_in_flight: Option<NonZeroUsize>,
+    /// Relative share of queries sent to this server
+    pub weight: NonZeroU32,
+}
+
+impl Default for UpstreamLimits {
+    fn default() -> Self {
+        Self {
+            rate_limit: None,
+            max_in_flight: None,
+            weight: default_weight(),
+        }
+    }
+}
+
+impl UpstreamLimits {
+    /// Apply a single option; `None` if it is not a valid limit
+    pub(crate) fn set_option(&mut self, option: &str) -> Option<()> {
+        let option = option.trim();
+        let (key, value) = match option.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => ("qps", option.strip_suffix("qps")?),
+        };
+
+        match key {
+            "qps" => self.rate_limit = Some(value.parse().ok()?),
+            "max" => self.max_in_flight = Some(value.parse().ok()?),
+            "weight" => self.weight = value.parse().ok()?,
+            _ => return None,
+        }
+        Some(())
+    }
+
+    /// Options that differ from the defaults, in canonical form
+    pub(crate) fn options(&self) -> Vec<String> {
+        let mut options = Vec::new();
+        if let Some(qps) = self.rate_limit {
+            options.push(format!("{}qps", qps));
+        }
+        if let Some(max) = self.max_in_flight {
+            options.push(format!("max={}", max));
+        }
+        if self.weight.get() != 1 {
+            options.push(format!("weight={}", self.weight));
+        }
+        options
+    }
+}
+
+/// Write `@OPT/OPT...`, or nothing if there are no options
+pub(crate) fn write_options(
+    f: &mut std::fmt::Formatter<'_>,
+    options: Vec<String>,
+) -> std::fmt::Result {
+    if options.is_empty() {
+        Ok(())
+    } else {
+        write!(f, "@{}", options.join("/"))
+    }
+}
+
+/// DNS-over-TLS server, written as `ADDR[:PORT][#TLS_NAME][@OPT[/OPT...]]`
+/// with the same options as [`UpstreamSpec`]:
+///
+/// ```text
+/// 1.1.1.1#cloudflare-dns.com
+/// [2620:fe::fe]:853#dns.quad9.net@20qps
+/// ```
+///
+/// The port defaults to 853. `TLS_NAME` is sent as SNI and must match the
+/// server's certificate; it defaults to the address itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DotSpec {
+    pub server: SocketAddr,
+    pub tls_name: String,
+    pub limits: UpstreamLimits,
+}
+
+/// Standard DNS-over-TLS port
+pub const DOT_PORT: u16 = 853;
+
+impl FromStr for DotSpec {
+    type Err = ReverDNSError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let invalid = || ReverDNSError::InvalidResolver(s.to_string());
+
+        let (server, options) = match s.split_once('@') {
+            Some((server, options)) => (server, Some(options)),
+            None => (s, None),
+        };
+        let (address, tls_name) = match server.split_once('#') {
+            Some((address, name)) => (address.trim(), Some(name.trim())),
+            None => (server.trim(), None),
+        };
+
+        let server = parse_server(address, DOT_PORT).ok_or_else(invalid)?;
+        let tls_name = match tls_name {
+            Some("") => return Err(invalid()),
+            Some(name) => name.to_string(),
+            None => server.ip().to_string(),
+        };
+
+        let mut limits = UpstreamLimits::default();
+        for option in options.into_iter().flat_map(|o| o.split('/')) {
+            limits.set_option(option).ok_or_else(invalid)?;
+        }
+
+        Ok(Self {
+            server,
+            tls_name,
+            limits,
+        })
+    }
+}
+
+impl std::fmt::Display for DotSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.server)?;
+        if self.tls_name != self.server.ip().to_string() {
+            write!(f, "#{}", self.tls_name)?;
+        }
+        write_options(f, self.limits.options())
+    }
+}
+
+/// A single upstream server with its own connection pool and limits
+pub struct Upstream {
+    name: String,
+    weight: u32,
+    transport: Transport,
+    validator: Option<Validator>,
+    limiter: Option<RateLimiter>,
+    in_flight: Option<Semaphore>,
+    health: Health,
+}
+
+/// How queries reach an upstream
+enum Transport {
+    /// DNS over UDP, TCP or TLS, through trust-dns
+    Resolver(Box<AsyncResolver<TruncationCheck>>),
+    /// DNS over HTTPS, through our own HTTP client
+    Https(DohClient),
+}
+
+/// Uncached path to the same server for PTR queries that are validated with
+/// DNSSEC, so the validator checks the very answer that is reported
+enum Validator {
+    Dns(Exchange),
+    Https(DohClient),
+}
+
+impl Upstream {
+    /// Plain DNS, over UDP unless the spec sets another transport
+    pub fn udp(spec: &UpstreamSpec, opts: &ResolverOpts) -> Self {
+        Self::dns(spec, TransportMode::Udp, opts)
+    }
+
+    /// Plain DNS, using `transport` unless the spec sets its own
+    pub fn dns(spec: &UpstreamSpec, transport: TransportMode, opts: &ResolverOpts) -> Self {
+        Self::plain(
+            spec.address,
+            &spec.limits(),
+            spec.transport.unwrap_or(transport),
+            ResolverConfig::new(),
+            opts,
+        )
+    }
+
+    /// A name server from the system configuration, queried with its
+    /// options and search list
+    pub fn system(
+        server: SocketAddr,
+        system: &SystemResolvers,
+        transport: TransportMode,
+        opts: &ResolverOpts,
+    ) -> Self {
+        Self::plain(
+            server,
+            &UpstreamLimits::default(),
+            transport,
+            system.resolver_config(),
+            &system.resolver_opts(opts),
+        )
+    }
+
+    fn plain(
+        socket_addr: SocketAddr,
+        limits: &UpstreamLimits,
+        transport: TransportMode,
+        config: ResolverConfig,
+        opts: &ResolverOpts,
+    ) -> Self {
+        let servers = transport
+            .protocols()
+            .iter()
+            .map(|&protocol| NameServerConfig {
+                socket_addr,
+                protocol,
+                tls_dns_name: None,
+                trust_negative_responses: true,
+                bind_addr: None,
+                tls_config: None,
+            })
+            .collect();
+
+        let mut opts = *opts;
+        // Also covers UDP being dropped or refused outright
+        opts.try_tcp_on_error = transport == TransportMode::UdpThenTcp;
+        Self::with_servers(
+            format!("{}/{}", socket_addr, transport.label()),
+            limits,
+            config,
+            servers,
+            &opts,
+        )
+    }
+
+    /// DNS over TLS, checking the server certificate against `spec.tls_name`
+    pub fn tls(spec: &DotSpec, tls: Arc<ClientConfig>, opts: &ResolverOpts) -> Self {
+        let server = NameServerConfig {
+            socket_addr: spec.server,
+            protocol: Protocol::Tls,
+            tls_dns_name: Some(spec.tls_name.clone()),
+            trust_negative_responses: true,
+            bind_addr: None,
+            tls_config: Some(TlsClientConfig(tls)),
+        };
+        Self::with_server(format!("{}/tls", spec.server), &spec.limits, server, opts)
+    }
+
+    /// DNS over HTTPS, named after its URL without credentials; answers are
+    /// also validated if `opts.validate` is set
+    pub fn https(client: DohClient, limits: &UpstreamLimits, opts: &ResolverOpts) -> Self {
+        let validator = opts.validate.then(|| Validator::Https(client.clone()));
+        let mut name = client.url().clone();
+        let _ = name.set_username("");
+        let _ = name.set_password(None);
+        Self::with_transport(
+            name.to_string(),
+            limits,
+            Transport::Https(client),
+            validator,
+        )
+    }
+
+    /// Any name server configuration, labelled `name`
+    pub fn with_server(
+        name: String,
+        limits: &UpstreamLimits,
+        server: NameServerConfig,
+        opts: &ResolverOpts,
+    ) -> Self {
+        Self::with_servers(name, limits, ResolverConfig::new(), vec![server], opts)
+    }
+
+    /// One server reached over several protocols, tried in order. With
+    /// `opts.validate` set, PTR queries go through a validator instead.
+    fn with_servers(
+        name: String,
+        limits: &UpstreamLimits,
+        mut config: ResolverConfig,
+        servers: Vec<NameServerConfig>,
+        opts: &ResolverOpts,
+    ) -> Self {
+        let validator = opts
+            .validate
+            .then(|| Validator::Dns(Exchange::new(servers.clone(), opts)));
+
+        for server in servers {
+            // The resolver takes TLS settings from the server group, not
+            // from each server entry
+            if let Some(TlsClientConfig(tls)) = &server.tls_config {
+                config.set_tls_client_config(tls.clone());
+            }
+            config.add_name_server(server);
+        }
+
+        let mut opts = *opts;
+        opts.validate = false;
+        let resolver = AsyncResolver::new(config, opts, TruncationCheck::default());
+        Self::with_transport(
+            name,
+            limits,
+            Transport::Resolver(Box::new(resolver)),
+            validator,
+        )
+    }
+
+    fn with_transport(
+        name: String,
+        limits: &UpstreamLimits,
+        transport: Transport,
+        validator: Option<Validator>,
+    ) -> Self {
+        Self {
+            name,
+            weight: limits.weight.get(),
+            transport,
+            validator,
+            limiter: limits.rate_limit.map(|qps| RateLimiter::new(qps.get(), 1)),
+            in_flight: limits.max_in_flight.map(|max| Semaphore::new(max.get())),
+            health: Health::new(HealthPolicy::default()),
+        }
+    }
+
+    /// Server address and protocol, as recorded in results
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Relative share of queries
+    pub fn weight(&self) -> u32 {
+        self.weight
+    }
+
+    /// Replace the health policy, resetting the server's health record
+    pub fn set_health_policy(&mut self, policy: HealthPolicy) {
+        self.health = Health::new(policy);
+    }
+
+    /// Outcome counters and circuit breaker
+    pub fn health(&self) -> &Health {
+        &self.health
+    }
+
+    /// Current health, as reported in statistics
+    pub fn health_report(&self) -> UpstreamHealth {
+        self.health.snapshot(&self.name)
+    }
+
+    /// Recent response time, if any query has been answered
+    pub fn latency(&self) -> Option<Duration> {
+        self.health.latency()
+    }
+
+    /// Whether a query sent now would have to wait for this server's limits
+    pub fn is_saturated(&self) -> bool {
+        self.in_flight
+            .as_ref()
+            .is_some_and(|s| s.available_permits() == 0)
+            || !self.time_until_ready().is_zero()
+    }
+
+    /// Wait before this server's rate limit admits another query
+    pub fn time_until_ready(&self) -> Duration {
+        self.limiter
+            .as_ref()
+            .map_or(Duration::ZERO, |l| l.time_until_ready())
+    }
+
+    /// Wait for this server's rate limit and an in-flight slot. The returned
+    /// permit must be held for the duration of the query.
+    pub(crate) async fn admit(
+        &self,
+        max_wait: Option<Duration>,
+    ) -> Result<Option<SemaphorePermit<'_>>> {
+        match (&self.limiter, max_wait) {
+            (Some(limiter), Some(max_wait)) => limiter.acquire_within(max_wait).await?,
+            (Some(limiter), None) => limiter.acquire().await,
+            (None, _) => {}
+        }
+
+        match &self.in_flight {
+            Some(semaphore) => semaphore
+                .acquire()
+                .await
+                .map(Some)
+                .map_err(|e| ReverDNSError::InternalError(e.to_string())),
+            None => Ok(None),
+        }
+    }
+
+    /// Send a PTR query for `ip` to this server
+    pub(crate) async fn reverse_lookup(
+        &self,
+        ip: IpAddr,
+    ) -> std::result::Result<Lookup, ResolveError> {
+        self.lookup(Name::from(ip), RecordType::PTR).await
+    }
+
+    /// Send a query for `name` to this server
+    pub(crate) async fn lookup(
+        &self,
+        name: Name,
+        record_type: RecordType,
+    ) -> std::result::Result<Lookup, ResolveError> {
+        match &self.transport {
+            Transport::Resolver(resolver) => resolver.lookup(name, record_type).await,
+            Transport::Https(client) => client.query(Query::query(name, record_type)).await,
+        }
+    }
+
+    /// Whether answers from this server are validated with DNSSEC
+    pub fn validates(&self) -> bool {
+        self.validator.is_some()
+    }
+
+    /// Send a PTR query for `ip` to this server with DNSSEC validation, if
+    /// it validates. The inner `None` means no answer within `timeout`.
+    pub(crate) async fn validated_reverse_lookup(
+        &self,
+        ip: IpAddr,
+        timeout: Duration,
+    ) -> Option<Option<Validated>> {
+        let query = Query::query(Name::from(ip), RecordType::PTR);
+        Some(match self.validator.as_ref()? {
+            Validator::Dns(exchange) => {
+                dnssec::validated_lookup(exchange.clone(), query, timeout).await
+            }
+            Validator::Https(client) => {
+                dnssec::validated_lookup(client.clone(), query, timeout).await
+            }
+        })
+    }
+}
+
+impl std::fmt::Debug for Upstream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Upstream")
+            .field("name", &self.name)
+            .field("weight", &self.weight)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_plain_address() {
+        let spec = UpstreamSpec::from_str("8.8.8.8").unwrap();
+        assert_eq!(spec, UpstreamSpec::new("8.8.8.8".parse().unwrap()));
+        assert_eq!(spec.to_string(), "8.8.8.8");
+        assert!(UpstreamSpec::from_str("2606:4700::1111").is_ok());
+    }
+
+    #[test]
+    fn test_parse_ports() {
+        let spec = UpstreamSpec::from_str("127.0.0.1:5353@50qps").unwrap();
+        assert_eq!(spec.address, "127.0.0.1:5353".parse().unwrap());
+        assert_eq!(spec.to_string(), "127.0.0.1:5353@50qps");
+
+        let spec = UpstreamSpec::from_str("[2001:db8::1]:5300").unwrap();
+        assert_eq!(spec.address, "[2001:db8::1]:5300".parse().unwrap());
+        assert_eq!(spec.to_string(), "[2001:db8::1]:5300");
+
+        // Brackets without a port, and the default port written out
+        let spec = UpstreamSpec::from_str("[2001:db8::1]").unwrap();
+        assert_eq!(spec.address.port(), 53);
+        assert_eq!(spec.to_string(), "2001:db8::1");
+        assert_eq!(
+            UpstreamSpec::from_str("8.8.8.8:53").unwrap().to_string(),
+            "8.8.8.8"
+        );
+
+        let opts = ResolverOpts::default();
+        let spec = UpstreamSpec::from_str("[::1]:5300").unwrap();
+        assert_eq!(Upstream::udp(&spec, &opts).name(), "[::1]:5300/udp");
+    }
+
+    #[test]
+    fn test_upstream_name() {
+        let opts = ResolverOpts::default();
+        let spec = UpstreamSpec::from_str("8.8.8.8@50qps").unwrap();
+        assert_eq!(Upstream::udp(&spec, &opts).name(), "8.8.8.8:53/udp");
+        let spec = UpstreamSpec::from_str("2606:4700::1111").unwrap();
+        assert_eq!(
+            Upstream::udp(&spec, &opts).name(),
+            "[2606:4700::1111]:53/udp"
+        );
+        assert_eq!(
+            Upstream::dns(&spec, TransportMode::UdpThenTcp, &opts).name(),
+            "[2606:4700::1111]:53/udp+tcp"
+        );
+        // The spec's own transport wins over the default
+        let spec = UpstreamSpec::from_str("8.8.8.8@transport=tcp").unwrap();
+        assert_eq!(Upstream::udp(&spec, &opts).name(), "8.8.8.8:53/tcp");
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let spec = UpstreamSpec::from_str("8.8.8.8@50qps/max=20/weight=3").unwrap();
+        assert_eq!(spec.rate_limit.map(|v| v.get()), Some(50));
+        assert_eq!(spec.max_in_flight.map(|v| v.get()), Some(20));
+        assert_eq!(spec.weight.get(), 3);
+        assert_eq!(spec.to_string(), "8.8.8.8@50qps/max=20/weight=3");
+
+        let spec = UpstreamSpec::from_str("10.0.0.53@qps=5000").unwrap();
+        assert_eq!(spec.rate_limit.map(|v| v.get()), Some(5000));
+
+        let spec = UpstreamSpec::from_str("10.0.0.53@transport=udp-then-tcp/weight=2").unwrap();
+        assert_eq!(spec.transport, Some(TransportMode::UdpThenTcp));
+        assert_eq!(spec.weight.get(), 2);
+        assert_eq!(
+            spec.to_string(),
+            "10.0.0.53@weight=2/transport=udp-then-tcp"
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for spec in [
+            "invalid",
+            "8.8.8.8@",
+            "8.8.8.8@0qps",
+            "8.8.8.8@max=0",
+            "8.8.8.8@fast",
+            "8.8.8.8@ttl=5",
+            "8.8.8.8@transport=quic",
+            "8.8.8.8:",
+            "8.8.8.8:99999",
+            "[8.8.8.8]:53",
+            "[2001:db8::1",
+            "2001:db8::1]:53",
+        ] {
+            assert!(UpstreamSpec::from_str(spec).is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn test_parse_dot() {
+        let spec = DotSpec::from_str("1.1.1.1#cloudflare-dns.com@50qps").unwrap();
+        assert_eq!(spec.server, "1.1.1.1:853".parse().unwrap());
+        assert_eq!(spec.tls_name, "cloudflare-dns.com");
+        assert_eq!(spec.limits.rate_limit.map(|v| v.get()), Some(50));
+        assert_eq!(spec.to_string(), "1.1.1.1:853#cloudflare-dns.com@50qps");
+
+        let spec = DotSpec::from_str("[2620:fe::fe]:8853").unwrap();
+        assert_eq!(spec.server.port(), 8853);
+        assert_eq!(spec.tls_name, "2620:fe::fe");
+        assert_eq!(spec.to_string(), "[2620:fe::fe]:8853");
+        assert_eq!(DotSpec::from_str("2620:fe::fe").unwrap().server.port(), 853);
+
+        for spec in ["dns.quad9.net", "1.1.1.1#", "1.1.1.1:99999", "1.1.1.1@fast"] {
+            assert!(DotSpec::from_str(spec).is_err(), "{}", spec);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upstream_limits() {
+        let spec = UpstreamSpec::from_str("192.0.2.53@1qps/max=1").unwrap();
+        let upstream = Upstream::udp(&spec, &ResolverOpts::default());
+        assert!(!upstream.is_saturated());
+
+        let permit = upstream.admit(None).await.unwrap();
+        assert!(permit.is_some());
+        assert!(upstream.is_saturated());
+        drop(permit);
+
+        // The in-flight slot is free again but the rate limit still applies
+        assert!(upstream.is_saturated());
+        assert!(upstream
+            .admit(Some(Duration::from_millis(10)))
+            .await
+            .is_err());
+    }
+}
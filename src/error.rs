@@ -55,6 +55,9 @@ pub enum ReverDNSError {
     #[error("Permission denied: {0}")]
     PermissionDenied(String),
 
+    #[error("Interrupted before all lookups completed")]
+    Interrupted,
+
     #[error("Internal error: {0}")]
     InternalError(String),
 
@@ -83,6 +86,7 @@ impl ReverDNSError {
             ReverDNSError::Timeout => 5,
             ReverDNSError::NetworkError(_) => 6,
             ReverDNSError::RetryLimitExceeded => 7,
+            ReverDNSError::Interrupted => 130,
             _ => 1,
         }
     }
@@ -104,5 +108,6 @@ mod tests {
         assert_eq!(ReverDNSError::InvalidIpAddress("test".to_string()).exit_code(), 1);
         assert_eq!(ReverDNSError::Timeout.exit_code(), 5);
         assert_eq!(ReverDNSError::FileNotFound("test".to_string()).exit_code(), 3);
+        assert_eq!(ReverDNSError::Interrupted.exit_code(), 130);
     }
 }
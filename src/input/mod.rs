@@ -0,0 +1,170 @@
+pub mod range;
+pub mod reader;
+
+pub use range::{IpRange, IpRangeIter, DEFAULT_MAX_RANGE_SIZE};
+pub use reader::{AddressIter, RangeReader};
+
+use crate::error::{Result, ReverDNSError};
+use std::path::{Path, PathBuf};
+
+/// Boxed stream of ranges, as produced by [`InputSource::ranges`]
+pub type RangeStream = Box<dyn Iterator<Item = Result<IpRange>> + Send>;
+
+/// Parse address specifications given on the command line
+pub fn parse_specs<S: AsRef<str>>(specs: &[S], limit: Option<u128>) -> Result<Vec<IpRange>> {
+    specs
+        .iter()
+        .map(|spec| IpRange::parse(spec.as_ref(), limit))
+        .collect()
+}
+
+/// Read all address specifications from a file, one per line.
+///
+/// See [`RangeReader`] for the accepted syntax; prefer [`InputSource`] for
+/// large files.
+pub fn read_ranges_from_file(path: &Path, limit: Option<u128>) -> Result<Vec<IpRange>> {
+    RangeReader::open(path, limit)?.collect()
+}
+
+/// Total number of addresses covered by `ranges`
+pub fn total_addresses(ranges: &[IpRange]) -> u128 {
+    ranges
+        .iter()
+        .fold(0u128, |total, range| total.saturating_add(range.len()))
+}
+
+/// All addresses to look up: command-line specifications followed by the
+/// contents of an optional input file, read on demand
+#[derive(Debug, Clone)]
+pub struct InputSource {
+    specs: Vec<IpRange>,
+    file: Option<PathBuf>,
+    limit: Option<u128>,
+}
+
+impl InputSource {
+    /// Parse command-line specifications and remember the input file
+    pub fn new<S: AsRef<str>>(
+        specs: &[S],
+        file: Option<PathBuf>,
+        limit: Option<u128>,
+    ) -> Result<Self> {
+        if let Some(path) = &file {
+            if !path.exists() {
+                return Err(ReverDNSError::FileNotFound(path.display().to_string()));
+            }
+        }
+
+        Ok(Self {
+            specs: parse_specs(specs, limit)?,
+            file,
+            limit,
+        })
+    }
+
+    /// Count the addresses without holding them in memory. This reads the
+    /// whole input file once and fails on the first oversize range.
+    pub fn count(&self) -> Result<u128> {
+        let mut total = total_addresses(&self.specs);
+        for range in self.file_ranges()? {
+            total = total.saturating_add(range?.len());
+        }
+        Ok(total)
+    }
+
+    /// Number of addresses, if it is known without reading the input file:
+    /// only command-line specifications are counted up front, so this is
+    /// `None` whenever a file is given
+    pub fn known_count(&self) -> Option<u128> {
+        match self.file {
+            Some(_) => None,
+            None => Some(total_addresses(&self.specs)),
+        }
+    }
+
+    /// Stream every range in input order
+    pub fn ranges(&self) -> Result<RangeStream> {
+        let specs = self.specs.clone().into_iter().map(Ok);
+        Ok(Box::new(specs.chain(self.file_ranges()?)))
+    }
+
+    /// Stream every address in input order
+    pub fn addresses(&self) -> Result<AddressIter<RangeStream>> {
+        Ok(AddressIter::new(self.ranges()?))
+    }
+
+    fn file_ranges(&self) -> Result<RangeStream> {
+        match &self.file {
+            Some(path) => Ok(Box::new(RangeReader::open(path, self.limit)?)),
+            None => Ok(Box::new(std::iter::empty())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_read_ranges_from_file() {
+        let file = NamedTempFile::new().unwrap();
+        fs::write(
+            file.path(),
+            "# comment\n8.8.8.8\n\n10.0.0.0/30\nbogus\n192.168.1.*\n",
+        )
+        .unwrap();
+
+        let ranges = read_ranges_from_file(file.path(), Some(DEFAULT_MAX_RANGE_SIZE)).unwrap();
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(total_addresses(&ranges), 1 + 4 + 256);
+    }
+
+    #[test]
+    fn test_read_ranges_rejects_large_range() {
+        let file = NamedTempFile::new().unwrap();
+        fs::write(file.path(), "10.0.0.0/8\n").unwrap();
+
+        assert!(read_ranges_from_file(file.path(), Some(DEFAULT_MAX_RANGE_SIZE)).is_err());
+        assert!(read_ranges_from_file(file.path(), None).is_ok());
+    }
+
+    #[test]
+    fn test_parse_specs_rejects_invalid() {
+        assert!(parse_specs(&["8.8.8.8", "nope"], None).is_err());
+        assert_eq!(
+            parse_specs(&["8.8.8.8", "1.1.1.0/31"], None).unwrap().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_input_source_known_count() {
+        let source = InputSource::new(&["8.8.8.8", "10.0.0.0/30"], None, None).unwrap();
+        assert_eq!(source.known_count(), Some(5));
+    }
+
+    #[test]
+    fn test_input_source() {
+        let file = NamedTempFile::new().unwrap();
+        fs::write(file.path(), "10.0.0.0/30\n").unwrap();
+
+        let source = InputSource::new(
+            &["8.8.8.8"],
+            Some(file.path().to_path_buf()),
+            Some(DEFAULT_MAX_RANGE_SIZE),
+        )
+        .unwrap();
+        assert_eq!(source.count().unwrap(), 5);
+        assert_eq!(source.known_count(), None);
+
+        let ips: Vec<String> = source
+            .addresses()
+            .unwrap()
+            .map(|ip| ip.unwrap().to_string())
+            .collect();
+        assert_eq!(ips.first().unwrap(), "8.8.8.8");
+        assert_eq!(ips.last().unwrap(), "10.0.0.3");
+    }
+}
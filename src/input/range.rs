@@ -0,0 +1,314 @@
+use crate::error::{Result, ReverDNSError};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// Largest number of addresses a single range may expand to unless
+/// `--allow-large-ranges` is given (a full IPv4 /16)
+pub const DEFAULT_MAX_RANGE_SIZE: u128 = 65_536;
+
+/// An inclusive range of IP addresses of a single family.
+///
+/// Parsed from any of:
+/// - a single address: `8.8.8.8`, `2001:db8::1`
+/// - a CIDR prefix: `10.0.0.0/24`, `2001:db8::/120`
+/// - an explicit range: `10.0.0.5-10.0.0.40` or `10.0.0.5-40`
+/// - trailing IPv4 wildcards: `192.168.1.*`, `10.1.*.*`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpRange {
+    start: IpAddr,
+    end: IpAddr,
+}
+
+impl IpRange {
+    /// Range holding a single address
+    pub fn single(ip: IpAddr) -> Self {
+        Self { start: ip, end: ip }
+    }
+
+    /// Build a range from inclusive bounds of the same family
+    pub fn new(start: IpAddr, end: IpAddr) -> Result<Self> {
+        let valid = match (start, end) {
+            (IpAddr::V4(s), IpAddr::V4(e)) => u32::from(s) <= u32::from(e),
+            (IpAddr::V6(s), IpAddr::V6(e)) => u128::from(s) <= u128::from(e),
+            _ => false,
+        };
+        if !valid {
+            return Err(ReverDNSError::InvalidIpAddress(format!(
+                "{}-{}",
+                start, end
+            )));
+        }
+        Ok(Self { start, end })
+    }
+
+    /// Parse a range specification, rejecting ranges larger than `limit`
+    /// addresses (`None` disables the check)
+    pub fn parse(spec: &str, limit: Option<u128>) -> Result<Self> {
+        let spec = spec.trim();
+        let invalid = || ReverDNSError::InvalidIpAddress(spec.to_string());
+
+        let range = if let Some((addr, prefix)) = spec.split_once('/') {
+            let addr = IpAddr::from_str(addr).map_err(|_| invalid())?;
+            let prefix = u8::from_str(prefix).map_err(|_| invalid())?;
+            Self::from_cidr(addr, prefix).ok_or_else(invalid)?
+        } else if let Some((start, end)) = spec.split_once('-') {
+            let start = IpAddr::from_str(start.trim()).map_err(|_| invalid())?;
+            let end = match (start, end.trim()) {
+                // `10.0.0.5-40` shorthand: only the last octet is given
+                (IpAddr::V4(s), end) if !end.contains('.') => {
+                    let last = u8::from_str(end).map_err(|_| invalid())?;
+                    let [a, b, c, _] = s.octets();
+                    IpAddr::V4(Ipv4Addr::new(a, b, c, last))
+                }
+                (_, end) => IpAddr::from_str(end).map_err(|_| invalid())?,
+            };
+            Self::new(start, end).map_err(|_| invalid())?
+        } else if spec.contains('*') {
+            Self::from_wildcard(spec).ok_or_else(invalid)?
+        } else {
+            Self::single(IpAddr::from_str(spec).map_err(|_| invalid())?)
+        };
+
+        if let Some(limit) = limit {
+            if range.len() > limit {
+                return Err(ReverDNSError::ConfigError(format!(
+                    "Range {} expands to {} addresses (limit {}); use --allow-large-ranges to \
+                     override",
+                    spec,
+                    range.len(),
+                    limit
+                )));
+            }
+        }
+
+        Ok(range)
+    }
+
+    fn from_cidr(addr: IpAddr, prefix: u8) -> Option<Self> {
+        match addr {
+            IpAddr::V4(v4) if prefix <= 32 => {
+                let host_bits = 32 - u32::from(prefix);
+                let mask = u32::MAX.checked_shl(host_bits).unwrap_or(0);
+                let start = u32::from(v4) & mask;
+                let end = start | !mask;
+                Some(Self {
+                    start: IpAddr::V4(Ipv4Addr::from(start)),
+                    end: IpAddr::V4(Ipv4Addr::from(end)),
+                })
+            }
+            IpAddr::V6(v6) if prefix <= 128 => {
+                let host_bits = 128 - u32::from(prefix);
+                let mask = u128::MAX.checked_shl(host_bits).unwrap_or(0);
+                let start = u128::from(v6) & mask;
+                let end = start | !mask;
+                Some(Self {
+                    start: IpAddr::V6(Ipv6Addr::from(start)),
+                    end: IpAddr::V6(Ipv6Addr::from(end)),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn from_wildcard(spec: &str) -> Option<Self> {
+        let parts: Vec<&str> = spec.split('.').collect();
+        if parts.len() != 4 {
+            return None;
+        }
+
+        // Wildcards must be trailing so the result is a contiguous prefix
+        let fixed = parts.iter().take_while(|p| **p != "*").count();
+        if parts[fixed..].iter().any(|p| *p != "*") {
+            return None;
+        }
+
+        let mut octets = [0u8; 4];
+        for (octet, part) in octets.iter_mut().zip(&parts[..fixed]) {
+            *octet = u8::from_str(part).ok()?;
+        }
+        Self::from_cidr(IpAddr::V4(Ipv4Addr::from(octets)), (fixed * 8) as u8)
+    }
+
+    /// First address of the range
+    pub fn start(&self) -> IpAddr {
+        self.start
+    }
+
+    /// Last address of the range
+    pub fn end(&self) -> IpAddr {
+        self.end
+    }
+
+    /// Number of addresses in the range (saturates for a full IPv6 /0)
+    pub fn len(&self) -> u128 {
+        let (start, end) = self.bounds();
+        (end - start).saturating_add(1)
+    }
+
+    /// Ranges always contain at least one address
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Lazily iterate over every address in the range
+    pub fn iter(&self) -> IpRangeIter {
+        let (next, end) = self.bounds();
+        IpRangeIter {
+            next: Some(next),
+            end,
+            v4: self.start.is_ipv4(),
+        }
+    }
+
+    fn bounds(&self) -> (u128, u128) {
+        match (self.start, self.end) {
+            (IpAddr::V4(s), IpAddr::V4(e)) => (u32::from(s) as u128, u32::from(e) as u128),
+            (IpAddr::V6(s), IpAddr::V6(e)) => (u128::from(s), u128::from(e)),
+            _ => unreachable!("range bounds always share an address family"),
+        }
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = ReverDNSError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s, Some(DEFAULT_MAX_RANGE_SIZE))
+    }
+}
+
+impl IntoIterator for &IpRange {
+    type Item = IpAddr;
+    type IntoIter = IpRangeIter;
+
+    fn into_iter(self) -> IpRangeIter {
+        self.iter()
+    }
+}
+
+impl std::fmt::Display for IpRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.start == self.end {
+            write!(f, "{}", self.start)
+        } else {
+            write!(f, "{}-{}", self.start, self.end)
+        }
+    }
+}
+
+/// Lazy iterator over the addresses of an [`IpRange`]
+#[derive(Debug, Clone)]
+pub struct IpRangeIter {
+    next: Option<u128>,
+    end: u128,
+    v4: bool,
+}
+
+impl Iterator for IpRangeIter {
+    type Item = IpAddr;
+
+    fn next(&mut self) -> Option<IpAddr> {
+        let current = self.next?;
+        self.next = if current < self.end {
+            Some(current + 1)
+        } else {
+            None
+        };
+
+        Some(if self.v4 {
+            IpAddr::V4(Ipv4Addr::from(current as u32))
+        } else {
+            IpAddr::V6(Ipv6Addr::from(current))
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.next {
+            Some(next) => match (self.end - next)
+                .checked_add(1)
+                .and_then(|remaining| usize::try_from(remaining).ok())
+            {
+                Some(remaining) => (remaining, Some(remaining)),
+                None => (usize::MAX, None),
+            },
+            None => (0, Some(0)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(spec: &str) -> Vec<String> {
+        IpRange::from_str(spec)
+            .unwrap()
+            .iter()
+            .map(|ip| ip.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_single_address() {
+        assert_eq!(expand("8.8.8.8"), vec!["8.8.8.8"]);
+        assert_eq!(expand(" 2001:db8::1 "), vec!["2001:db8::1"]);
+    }
+
+    #[test]
+    fn test_cidr() {
+        let ips = expand("10.0.0.0/30");
+        assert_eq!(ips, vec!["10.0.0.0", "10.0.0.1", "10.0.0.2", "10.0.0.3"]);
+        assert_eq!(IpRange::from_str("10.0.0.77/24").unwrap().len(), 256);
+        assert_eq!(expand("10.0.0.1/32"), vec!["10.0.0.1"]);
+        assert_eq!(expand("2001:db8::/127"), vec!["2001:db8::", "2001:db8::1"]);
+    }
+
+    #[test]
+    fn test_explicit_range() {
+        assert_eq!(IpRange::from_str("10.0.0.5-10.0.0.40").unwrap().len(), 36);
+        assert_eq!(expand("10.0.0.254-10.0.1.1").len(), 4);
+        assert_eq!(
+            expand("10.0.0.5-7"),
+            vec!["10.0.0.5", "10.0.0.6", "10.0.0.7"]
+        );
+        assert!(IpRange::from_str("10.0.0.40-10.0.0.5").is_err());
+        assert!(IpRange::from_str("10.0.0.1-2001:db8::1").is_err());
+    }
+
+    #[test]
+    fn test_wildcard() {
+        let range = IpRange::from_str("192.168.1.*").unwrap();
+        assert_eq!(range.len(), 256);
+        assert_eq!(range.start().to_string(), "192.168.1.0");
+        assert_eq!(range.end().to_string(), "192.168.1.255");
+        assert_eq!(IpRange::from_str("10.1.*.*").unwrap().len(), 65_536);
+        assert!(IpRange::from_str("192.168.*.1").is_err());
+    }
+
+    #[test]
+    fn test_invalid_specs() {
+        for spec in ["", "not-an-ip", "10.0.0.0/33", "10.0.0.0/x", "1.2.3.*.*"] {
+            assert!(IpRange::from_str(spec).is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn test_size_limit() {
+        let err = IpRange::from_str("10.0.0.0/8").unwrap_err();
+        assert!(matches!(err, ReverDNSError::ConfigError(_)));
+
+        let range = IpRange::parse("10.0.0.0/8", None).unwrap();
+        assert_eq!(range.len(), 16_777_216);
+        // Expansion is lazy, so huge ranges are cheap to iterate partially
+        assert_eq!(range.iter().nth(1).unwrap().to_string(), "10.0.0.1");
+
+        let range = IpRange::parse("::/0", None).unwrap();
+        assert_eq!(range.len(), u128::MAX);
+        assert_eq!(range.iter().size_hint(), (usize::MAX, None));
+
+        let mut iter = IpRange::from_str("10.0.0.0/30").unwrap().iter();
+        assert_eq!(iter.size_hint(), (4, Some(4)));
+        iter.nth(3);
+        assert_eq!(iter.size_hint(), (0, Some(0)));
+    }
+}
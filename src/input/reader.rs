@@ -0,0 +1,155 @@
+use crate::error::{Result, ReverDNSError};
+use crate::input::range::{IpRange, IpRangeIter};
+use std::fs;
+use std::io::{self, BufRead};
+use std::net::IpAddr;
+use std::path::Path;
+use tracing::warn;
+
+/// Streams [`IpRange`]s from a line-oriented source, one specification per
+/// line.
+///
+/// Blank lines and lines starting with `#` are skipped. Malformed entries are
+/// logged and skipped; ranges above `limit` and read failures are yielded as
+/// errors.
+pub struct RangeReader<R> {
+    lines: io::Lines<R>,
+    line_no: usize,
+    source: String,
+    limit: Option<u128>,
+}
+
+impl RangeReader<io::BufReader<fs::File>> {
+    /// Open a file for streaming
+    pub fn open(path: &Path, limit: Option<u128>) -> Result<Self> {
+        if !path.exists() {
+            return Err(ReverDNSError::FileNotFound(path.display().to_string()));
+        }
+        let file = fs::File::open(path)?;
+        Ok(Self::new(
+            io::BufReader::new(file),
+            path.display().to_string(),
+            limit,
+        ))
+    }
+}
+
+impl<R: BufRead> RangeReader<R> {
+    /// Wrap a reader; `source` names it in log messages
+    pub fn new(reader: R, source: impl Into<String>, limit: Option<u128>) -> Self {
+        Self {
+            lines: reader.lines(),
+            line_no: 0,
+            source: source.into(),
+            limit,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for RangeReader<R> {
+    type Item = Result<IpRange>;
+
+    fn next(&mut self) -> Option<Result<IpRange>> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.into())),
+            };
+            self.line_no += 1;
+
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            match IpRange::parse(trimmed, self.limit) {
+                Ok(range) => return Some(Ok(range)),
+                Err(ReverDNSError::InvalidIpAddress(spec)) => {
+                    warn!(
+                        "{}:{}: skipping invalid entry {}",
+                        self.source, self.line_no, spec
+                    );
+                }
+                Err(e) => {
+                    return Some(Err(ReverDNSError::ConfigError(format!(
+                        "{}:{}: {}",
+                        self.source, self.line_no, e
+                    ))))
+                }
+            }
+        }
+    }
+}
+
+/// Lazily expands a sequence of ranges into individual addresses
+pub struct AddressIter<I> {
+    ranges: I,
+    current: Option<IpRangeIter>,
+}
+
+impl<I> AddressIter<I>
+where
+    I: Iterator<Item = Result<IpRange>>,
+{
+    pub fn new(ranges: I) -> Self {
+        Self {
+            ranges,
+            current: None,
+        }
+    }
+}
+
+impl<I> Iterator for AddressIter<I>
+where
+    I: Iterator<Item = Result<IpRange>>,
+{
+    type Item = Result<IpAddr>;
+
+    fn next(&mut self) -> Option<Result<IpAddr>> {
+        loop {
+            if let Some(ip) = self.current.as_mut().and_then(Iterator::next) {
+                return Some(Ok(ip));
+            }
+            match self.ranges.next()? {
+                Ok(range) => self.current = Some(range.iter()),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_reader_skips_comments_and_invalid() {
+        let data = "# header\n8.8.8.8\n\nbogus\n10.0.0.0/31\n";
+        let ranges: Vec<IpRange> = RangeReader::new(data.as_bytes(), "test", None)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(ranges.len(), 2);
+    }
+
+    #[test]
+    fn test_range_reader_reports_oversize_line() {
+        let data = "8.8.8.8\n10.0.0.0/8\n";
+        let mut reader = RangeReader::new(data.as_bytes(), "test", Some(256));
+        assert!(reader.next().unwrap().is_ok());
+        let err = reader.next().unwrap().unwrap_err();
+        assert!(err.to_string().contains("test:2"), "{}", err);
+    }
+
+    #[test]
+    fn test_address_iter_is_lazy() {
+        let data = "10.0.0.0/30\n192.168.0.0/8\n";
+        let ips: Vec<String> = AddressIter::new(RangeReader::new(data.as_bytes(), "test", None))
+            .take(5)
+            .map(|ip| ip.unwrap().to_string())
+            .collect();
+        assert_eq!(
+            ips,
+            vec!["10.0.0.0", "10.0.0.1", "10.0.0.2", "10.0.0.3", "192.0.0.0"]
+        );
+    }
+}
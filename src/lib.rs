@@ -1,18 +1,28 @@
 //! ReverDNS - High-performance reverse DNS lookup tool
 //!
 //! This library provides functionality for bulk reverse DNS (PTR) lookups with support for:
-//! - JSON and CSV output formats
-//! - Resolver rotation
-//! - Rate limiting
-//! - DNS-over-HTTPS
+//! - JSON and CSV output formats, streamed as results arrive
+//! - CIDR, range and wildcard input expansion
+//! - Checkpointing and resumption of interrupted runs
+//! - Resolver rotation strategies with per-resolver rate limits and concurrency caps
+//! - Resolver health tracking with automatic ejection and recovery
+//! - Global token-bucket rate limiting
+//! - DNS-over-HTTPS and DNS-over-TLS
+//! - HTTP API server with async job submission
+//! - TOML configuration files layered under CLI arguments
 //! - Comprehensive error handling
 //! - Async/await with Tokio
 
+pub mod checkpoint;
 pub mod cli;
+pub mod config;
 pub mod dns;
 pub mod error;
+pub mod input;
 pub mod logger;
 pub mod output;
+pub mod pipeline;
+pub mod web;
 
 pub use error::{Result, ReverDNSError};
 pub use dns::{DnsResolver, LookupResult, LookupStatus};
@@ -1,30 +1,81 @@
-use clap::Parser;
+use clap::CommandFactory;
 use colored::*;
-use futures::{stream, StreamExt};
 use indicatif::{ProgressBar, ProgressStyle};
 use reverdns::{
-    cli::Args,
-    dns::DnsResolver,
+    checkpoint::{self, Checkpoint, CompletedSet},
+    cli::{Args, CacheAction, Command, LogFormat},
+    config,
+    dns::{
+        system::RESOLV_CONF, CacheDb, DnsResolver, HealthState, RateLimiter, SystemResolvers,
+        UpstreamConfig, UpstreamHealth, DEFAULT_DOH_PROVIDER,
+    },
     error::Result,
+    input::InputSource,
     logger,
-    output::{format_csv, format_json},
+    output::{self, ResultWriter, RunSummary},
+    pipeline::{run_pipeline, PipelineOptions},
+    web,
 };
 use std::fs;
-use std::io::{self, BufRead};
-use std::path::Path;
+use std::io::{self, BufWriter};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tracing::{error, info};
+use tracing::{debug, error, info, warn};
 
 #[tokio::main]
 async fn main() {
-    let args = Args::parse();
+    dotenv::dotenv().ok();
+    let matches = Args::command().get_matches();
+
+    // Merge configuration file settings underneath the command line
+    let (mut args, loaded_config) = match config::resolve_args(&matches) {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            eprintln!("{} {}", "Error:".red().bold(), e);
+            std::process::exit(e.exit_code());
+        }
+    };
+
+    // resolv.conf options sit beneath the configuration file
+    if args.system_resolvers {
+        match SystemResolvers::load() {
+            Ok(system) => config::apply_system_resolvers(
+                &mut args,
+                &matches,
+                loaded_config.as_ref().map(|l| &l.config),
+                system,
+            ),
+            Err(e) => {
+                eprintln!("{} {}", "Error:".red().bold(), e);
+                std::process::exit(e.exit_code());
+            }
+        }
+    }
 
     // Initialize logger
-    if let Err(e) = logger::init_logger(&args.log_level.to_string()) {
+    let log_result = match args.log_format {
+        LogFormat::Text => logger::init_logger(&args.log_level.to_string()),
+        LogFormat::Json => logger::init_json_logger(&args.log_level.to_string()),
+    };
+    if let Err(e) = log_result {
         eprintln!("Failed to initialize logger: {}", e);
         std::process::exit(1);
     }
 
+    if let Some(loaded) = &loaded_config {
+        info!("Loaded configuration from {}", loaded.path.display());
+        for key in loaded.config.unsupported_keys() {
+            warn!(
+                "{}: `{}` is not supported yet and is ignored",
+                loaded.path.display(),
+                key
+            );
+        }
+    }
+
     print_banner();
 
     // Validate arguments
@@ -35,7 +86,15 @@ async fn main() {
     }
 
     // Run the application
-    if let Err(e) = run(args).await {
+    let outcome = if let Some(Command::Cache { action }) = &args.command {
+        run_cache(&args, action)
+    } else if args.web_server {
+        run_web_server(args).await
+    } else {
+        run(args).await
+    };
+
+    if let Err(e) = outcome {
         error!("Application error: {}", e);
         eprintln!("{} {}", "Error:".red().bold(), e);
         std::process::exit(e.exit_code());
@@ -67,180 +126,378 @@ fn print_banner() {
 async fn run(args: Args) -> Result<()> {
     let start_time = Instant::now();
 
-    // Collect IPs from arguments and/or input file
-    let mut ips = args.ips.clone();
-
-    if let Some(input_file) = &args.input {
-        info!("Reading IPs from file: {}", input_file);
-        let file_ips = read_ips_from_file(input_file)?;
-        ips.extend(file_ips);
-    }
-
-    if ips.is_empty() {
+    // Collect address ranges from arguments and/or input file
+    let source = open_input(&args)?;
+    // The input file is not read ahead just to size the progress bar
+    let total = source.known_count();
+    if total == Some(0) {
         return Err(reverdns::ReverDNSError::ConfigError(
             "No IPs provided".to_string(),
         ));
     }
 
-    info!("Processing {} IP addresses", ips.len());
-    eprintln!(
-        "{} {} IP addresses to process",
-        "ℹ".blue().bold(),
-        ips.len()
-    );
+    if let Some(total) = total {
+        info!("Processing {} IP addresses", total);
+        eprintln!(
+            "{} {} IP addresses to process",
+            "ℹ".blue().bold(),
+            total
+        );
+    }
 
     // Create DNS resolver
-    let resolver = if !args.resolver.is_empty() || args.dns_over_https {
-        if !args.resolver.is_empty() {
-            info!("Using custom resolvers: {:?}", args.resolver);
-            eprintln!("{} Using custom resolvers", "ℹ".blue().bold());
-        }
-        if args.dns_over_https {
-            info!("Using DNS-over-HTTPS");
-            eprintln!(
-                "{} Using DNS-over-HTTPS ({})",
-                "ℹ".blue().bold(),
-                args.doh_provider.clone().unwrap_or_default()
-            );
+    let resolver = build_resolver(&args).await?;
+
+    // Open or resume the checkpoint
+    let (mut checkpoint, completed) = open_checkpoint(&args)?;
+    let resuming = completed.as_ref().is_some_and(|c| !c.is_empty());
+
+    // Open output
+    let writer_options = output::WriterOptions {
+        csv_hostnames: args.csv_hostnames,
+        verify_forward: args.verify_forward,
+        validate_dnssec: args.validate_dnssec,
+        cache: args.cache || args.cache_db.is_some(),
+        ndjson_summary: args.ndjson_summary,
+    };
+    let mut writer = if let Some(output_file) = &args.output {
+        info!("Writing results to file: {}", output_file);
+        eprintln!(
+            "{} Writing results to {}",
+            "✔".green().bold(),
+            output_file.white()
+        );
+        let path = Path::new(output_file);
+        if resuming {
+            output::resume_file_writer(args.format, path, &writer_options)?
+        } else {
+            output::create_file_writer(args.format, path, &writer_options)?
         }
-
-        DnsResolver::with_resolvers(
-            &args.resolver,
-            args.timeout,
-            args.retry_count,
-            args.retry_backoff,
-            args.dns_over_https,
-            args.doh_provider,
-        )
-        .await?
     } else {
-        info!("Using default resolvers");
-        DnsResolver::new(args.timeout, args.retry_count, args.retry_backoff).await?
+        output::create_writer(args.format, BufWriter::new(io::stdout()), &writer_options)?
     };
 
-    // Calculate rate limit interval
-    let rate_limit_interval = if args.rate_limit > 0 {
-        Duration::from_secs_f64(1.0 / args.rate_limit as f64)
-    } else {
-        Duration::from_micros(1) // Practically no limit
+    // Initialize Progress Bar, or a spinner when the total is unknown
+    let pb = match total {
+        Some(total) => {
+            let pb = ProgressBar::new(u64::try_from(total).unwrap_or(u64::MAX));
+            pb.set_style(ProgressStyle::default_bar()
+                .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({per_sec}, {eta})")
+                .unwrap()
+                .progress_chars("#>-"));
+            pb
+        }
+        None => {
+            let pb = ProgressBar::new_spinner();
+            pb.set_style(ProgressStyle::default_spinner()
+                .template("{spinner:.green} [{elapsed_precise}] {pos} looked up ({per_sec})")
+                .unwrap());
+            pb.enable_steady_tick(Duration::from_millis(100));
+            pb
+        }
     };
 
-    let resolver_ref = &resolver;
-
-    // Initialize Progress Bar
-    let pb = ProgressBar::new(ips.len() as u64);
-    pb.set_style(ProgressStyle::default_bar()
-        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({per_sec}, {eta})")
-        .unwrap()
-        .progress_chars("#>-"));
-
-    // Concurrent processing loop
-    let results = stream::iter(ips)
-        .map(|ip| {
-            let pb = pb.clone();
-            async move {
-                // Apply rate limit delay (simple approximation)
-                tokio::time::sleep(rate_limit_interval).await;
-                let result = resolver_ref.lookup(&ip).await;
-                pb.inc(1);
-                result
+    // Count the results completed by the previous run, and write them
+    // again for formats that cannot be appended to
+    let mut summary = RunSummary::new();
+    if let (Some(checkpoint), Some(completed), true) = (&checkpoint, &completed, resuming) {
+        eprintln!(
+            "{} Resuming: {} lookups already completed",
+            "ℹ".blue().bold(),
+            completed.len()
+        );
+        let rewrite = !output::appends_on_resume(args.format);
+        Checkpoint::replay(checkpoint.path(), |result| {
+            if rewrite {
+                writer.write_result(&result)?;
             }
-        })
-        .buffer_unordered(args.concurrency)
-        .collect::<Vec<_>>()
-        .await;
-
-    pb.finish_with_message("Done");
+            summary.record(&result);
+            Ok(())
+        })?;
+        pb.set_position(summary.total as u64);
+    }
 
-    // Unwrap results
-    let results: Vec<reverdns::LookupResult> = results
-        .into_iter()
-        .filter_map(|r| match r {
-            Ok(res) => Some(res),
-            Err(e) => {
-                error!("Unexpected error type in stream: {}", e);
-                None
+    // Stop pulling new addresses on Ctrl-C; in-flight lookups still finish
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let signal_flag = shutdown.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            signal_flag.store(true, Ordering::SeqCst);
+            eprintln!(
+                "\n{} Interrupted, finishing in-flight lookups (Ctrl-C again to abort)",
+                "⚠".yellow().bold()
+            );
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(reverdns::ReverDNSError::Interrupted.exit_code());
             }
-        })
-        .collect();
-
-    let elapsed = start_time.elapsed().as_millis();
+        }
+    });
 
-    // Format output
-    let output = match args.format {
-        reverdns::OutputFormat::Json => format_json(&results, elapsed)?,
-        reverdns::OutputFormat::Csv => format_csv(&results)?,
+    // Concurrent processing loop, writing results as they complete
+    let options = PipelineOptions {
+        concurrency: args.concurrency,
+        completed: completed.map(Arc::new),
+        shutdown: Some(shutdown),
     };
+    let outcome = run_pipeline(
+        &resolver,
+        source.addresses()?,
+        &options,
+        &mut writer,
+        checkpoint.as_mut(),
+        |_| pb.inc(1),
+    )
+    .await?;
+    summary.merge(&outcome.summary);
 
-    // Write output
-    if let Some(output_file) = &args.output {
-        info!("Writing results to file: {}", output_file);
-        eprintln!(
-            "{} Writing results to {}",
-            "✔".green().bold(),
-            output_file.white()
-        );
-        fs::write(output_file, &output)?;
-    } else {
-        println!("{}", output);
-    }
+    pb.finish_with_message("Done");
+
+    let elapsed = start_time.elapsed().as_millis();
+    let mut metadata = summary.to_metadata(elapsed);
+    metadata.resolvers = resolver.health_report();
+    writer.finish(&metadata)?;
 
     // Print statistics if requested
     if args.stats {
-        print_statistics(&results, elapsed);
+        print_statistics(&summary, elapsed);
+        print_resolver_health(&metadata.resolvers);
+    }
+
+    if outcome.interrupted {
+        if let Some(checkpoint) = &checkpoint {
+            eprintln!(
+                "{} Progress saved; rerun with --checkpoint {} --resume to continue",
+                "ℹ".blue().bold(),
+                checkpoint.path().display()
+            );
+        }
+        return Err(reverdns::ReverDNSError::Interrupted);
+    }
+
+    // The run is complete, so the checkpoint is no longer needed
+    if let Some(checkpoint) = checkpoint {
+        checkpoint.remove()?;
     }
 
     info!("Completed in {}ms", elapsed);
     Ok(())
 }
 
-fn read_ips_from_file(path: &str) -> Result<Vec<String>> {
-    if !Path::new(path).exists() {
-        return Err(reverdns::ReverDNSError::FileNotFound(path.to_string()));
+fn open_checkpoint(args: &Args) -> Result<(Option<Checkpoint>, Option<CompletedSet>)> {
+    let Some(path) = &args.checkpoint else {
+        return Ok((None, None));
+    };
+
+    let fingerprint = input_fingerprint(args)?;
+    if args.resume {
+        let (checkpoint, completed) = Checkpoint::resume(path, &fingerprint)?;
+        Ok((Some(checkpoint), Some(completed)))
+    } else {
+        Ok((Some(Checkpoint::create(path, &fingerprint)?), None))
     }
+}
 
-    let file = fs::File::open(path)?;
-    let reader = io::BufReader::new(file);
-
-    let ips: Vec<String> = reader
-        .lines()
-        .filter_map(|line| {
-            line.ok().and_then(|l| {
-                let trimmed = l.trim();
-                // Simple IP validation could go here, but we let the resolver handle strict parsing
-                if !trimmed.is_empty() && !trimmed.starts_with('#') {
-                    Some(trimmed.to_string())
-                } else {
-                    None
-                }
-            })
-        })
-        .collect();
-
-    Ok(ips)
+/// Identify the input of a run so a checkpoint is only resumed against the
+/// same addresses
+fn input_fingerprint(args: &Args) -> Result<String> {
+    let mut parts = args.ips.clone();
+    parts.push(args.allow_large_ranges.to_string());
+    if let Some(input_file) = &args.input {
+        parts.push(input_file.clone());
+        parts.push(checkpoint::file_fingerprint(Path::new(input_file))?);
+    }
+    Ok(checkpoint::fingerprint(&parts))
 }
 
-fn print_statistics(results: &[reverdns::LookupResult], total_time_ms: u128) {
-    use reverdns::LookupStatus;
+fn open_input(args: &Args) -> Result<InputSource> {
+    if let Some(input_file) = &args.input {
+        info!("Reading IPs from file: {}", input_file);
+    }
+    InputSource::new(
+        &args.ips,
+        args.input.as_ref().map(PathBuf::from),
+        args.range_limit(),
+    )
+}
 
-    let successful = results
-        .iter()
-        .filter(|r| r.status == LookupStatus::Success)
-        .count();
-    let failed = results.len() - successful;
-    let avg_latency = if results.is_empty() {
-        0.0
-    } else {
-        results.iter().map(|r| r.latency_ms as f64).sum::<f64>() / results.len() as f64
+async fn build_resolver(args: &Args) -> Result<DnsResolver> {
+    let mut resolver = build_upstreams(args)
+        .await?
+        .with_strategy(args.strategy)
+        .with_health_policy(args.health_policy())
+        .with_retry_policy(args.retry_policy())
+        .with_forward_verification(args.verify_forward)
+        .with_bogus_rejection(args.reject_bogus);
+    debug!("Resolver strategy: {}", resolver.strategy());
+
+    if let Some(cache) = args.lookup_cache() {
+        debug!("Caching up to {} results for at most {}s", args.cache_max_size, args.cache_ttl);
+        resolver = resolver.with_cache(Arc::new(cache));
+    }
+
+    if let Some(path) = &args.cache_db {
+        info!("Using cache database: {}", path.display());
+        eprintln!(
+            "{} Using cache database {}",
+            "ℹ".blue().bold(),
+            path.display()
+        );
+        resolver = resolver.with_cache_db(Arc::new(CacheDb::open(path, args.max_age())?));
+    }
+
+    // One limiter shared by every lookup, including concurrent web jobs
+    let limiter = RateLimiter::new(args.rate_limit, args.rate_burst.unwrap_or(1));
+    debug!(
+        "Rate limit: {}/s, burst {}",
+        limiter.rate(),
+        limiter.burst()
+    );
+    Ok(resolver.with_rate_limiter(
+        Arc::new(limiter),
+        args.rate_limit_wait.map(Duration::from_secs),
+    ))
+}
+
+async fn build_upstreams(args: &Args) -> Result<DnsResolver> {
+    let config = UpstreamConfig {
+        udp: args
+            .resolver
+            .iter()
+            .map(|s| s.parse())
+            .collect::<Result<Vec<_>>>()?,
+        transport: args.transport,
+        system: args.system_conf.clone(),
+        dot: args
+            .dot_resolver
+            .iter()
+            .map(|s| s.parse())
+            .collect::<Result<Vec<_>>>()?,
+        doh: if !args.dns_over_https {
+            Vec::new()
+        } else if args.doh_provider.is_empty() {
+            vec![DEFAULT_DOH_PROVIDER.parse()?]
+        } else {
+            args.doh_provider
+                .iter()
+                .map(|s| s.parse())
+                .collect::<Result<Vec<_>>>()?
+        },
+        doh_method: args.doh_method,
+        ca_file: args.ca_file.clone(),
+        validate_dnssec: args.validate_dnssec,
     };
 
+    if !config.udp.is_empty() {
+        info!("Using custom resolvers: {:?}", args.resolver);
+        eprintln!("{} Using custom resolvers", "ℹ".blue().bold());
+    }
+    if let Some(system) = &config.system {
+        info!("Using system resolvers from {}: {}", RESOLV_CONF, system);
+        eprintln!(
+            "{} Using system resolvers from {} ({})",
+            "ℹ".blue().bold(),
+            RESOLV_CONF,
+            system
+        );
+    }
+    if !config.dot.is_empty() {
+        info!("Using DNS-over-TLS: {:?}", args.dot_resolver);
+        eprintln!("{} Using DNS-over-TLS", "ℹ".blue().bold());
+    }
+    if !config.doh.is_empty() {
+        let providers: Vec<String> = config.doh.iter().map(|d| d.url.to_string()).collect();
+        info!("Using DNS-over-HTTPS: {:?}", providers);
+        eprintln!(
+            "{} Using DNS-over-HTTPS ({})",
+            "ℹ".blue().bold(),
+            providers.join(", ")
+        );
+    }
+
+    let resolver =
+        DnsResolver::from_config(&config, args.timeout, args.retry_count, args.retry_backoff)
+            .await?;
+
+    let upstreams: Vec<&str> = resolver.upstreams().iter().map(|u| u.name()).collect();
+    if config.udp.is_empty()
+        && config.system.is_none()
+        && config.dot.is_empty()
+        && config.doh.is_empty()
+    {
+        warn!("No resolvers configured, using public defaults");
+        eprintln!(
+            "{} No resolvers configured; using public defaults (see --resolver and --system-resolvers)",
+            "⚠".yellow().bold()
+        );
+    }
+    info!("Upstreams: {}", upstreams.join(", "));
+    eprintln!("{} Upstreams: {}", "ℹ".blue().bold(), upstreams.join(", "));
+    Ok(resolver)
+}
+
+fn run_cache(args: &Args, action: &CacheAction) -> Result<()> {
+    let path = args.cache_db.as_deref().ok_or_else(|| {
+        reverdns::ReverDNSError::ConfigError("The cache command needs --cache-db".to_string())
+    })?;
+    let db = CacheDb::open(path, args.max_age())?;
+
+    match action {
+        CacheAction::Stats => {
+            let stats = db.stats()?;
+            println!("{}", "=== Cache ===".yellow().bold());
+            println!("Database:      {}", path.display());
+            println!("Entries:       {}", stats.entries.to_string().cyan());
+            println!("Fresh:         {}", stats.fresh.to_string().green());
+            println!("Stale:         {}", stats.entries - stats.fresh);
+            for (status, count) in &stats.statuses {
+                println!("  {:<12} {}", status, count);
+            }
+            if let (Some(oldest), Some(newest)) = (stats.oldest, stats.newest) {
+                println!("Oldest:        {}", oldest.to_rfc3339());
+                println!("Newest:        {}", newest.to_rfc3339());
+            }
+        }
+        CacheAction::Prune => {
+            let removed = db.prune()?;
+            eprintln!(
+                "{} Removed {} stale results from {}",
+                "✔".green().bold(),
+                removed,
+                path.display()
+            );
+        }
+        CacheAction::Export { output } => {
+            let written = match output {
+                Some(file) => db.export(BufWriter::new(fs::File::create(file)?))?,
+                None => db.export(BufWriter::new(io::stdout()))?,
+            };
+            eprintln!("{} Exported {} results", "✔".green().bold(), written);
+        }
+    }
+    Ok(())
+}
+
+async fn run_web_server(args: Args) -> Result<()> {
+    let resolver = build_resolver(&args).await?;
+    let state = web::AppState::new(resolver, args.concurrency, args.timeout);
+
+    let addr = SocketAddr::new(args.web_bind, args.web_port);
+    eprintln!(
+        "{} Web API listening on {}",
+        "ℹ".blue().bold(),
+        addr.to_string().cyan()
+    );
+    web::serve(state, addr).await
+}
+
+fn print_statistics(summary: &RunSummary, total_time_ms: u128) {
     println!("\n{}", "=== Statistics ===".yellow().bold());
-    println!("Total lookups: {}", results.len().to_string().cyan());
-    println!("Successful:    {}", successful.to_string().green());
-    println!("Failed:        {}", failed.to_string().red());
+    println!("Total lookups: {}", summary.total.to_string().cyan());
+    println!("Successful:    {}", summary.successful.to_string().green());
+    println!("Failed:        {}", summary.failed.to_string().red());
 
-    if !results.is_empty() {
-        let rate = (successful as f64 / results.len() as f64) * 100.0;
+    if summary.total > 0 {
+        let rate = summary.success_rate();
         let color_rate = if rate > 90.0 {
             rate.to_string().green()
         } else if rate > 50.0 {
@@ -252,12 +509,43 @@ fn print_statistics(results: &[reverdns::LookupResult], total_time_ms: u128) {
     }
 
     println!("Total time:    {}ms", total_time_ms);
-    println!("Avg latency:   {:.2}ms", avg_latency);
+    println!("Avg latency:   {:.2}ms", summary.average_latency_ms());
+
+    if summary.cached() {
+        println!(
+            "Cache:         {} hits, {} misses ({:.2}% hit rate)",
+            summary.cache_hits.to_string().green(),
+            summary.cache_misses,
+            summary.cache_hit_rate()
+        );
+    }
 
     if total_time_ms > 0 {
         println!(
             "Throughput:    {:.2} lookups/sec",
-            (results.len() as f64 / total_time_ms as f64) * 1000.0
+            (summary.total as f64 / total_time_ms as f64) * 1000.0
+        );
+    }
+}
+
+fn print_resolver_health(resolvers: &[UpstreamHealth]) {
+    println!("\n{}", "=== Resolvers ===".yellow().bold());
+    for health in resolvers {
+        let state = match health.state {
+            HealthState::Healthy => health.state.to_string().green(),
+            HealthState::Ejected => health.state.to_string().red(),
+        };
+        let latency = health
+            .average_latency_ms
+            .map_or_else(|| "-".to_string(), |ms| format!("{:.2}ms", ms));
+        println!(
+            "{:<28} {:<8} {:>8} queries  {:>6.2}% ok  avg {:>9}  ejected {}x",
+            health.resolver,
+            state,
+            health.queries(),
+            health.success_rate(),
+            latency,
+            health.ejections
         );
     }
 }
@@ -265,10 +553,12 @@ fn print_statistics(results: &[reverdns::LookupResult], total_time_ms: u128) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use clap::Parser;
 
     #[test]
     fn test_read_ips_from_file_not_found() {
-        let result = read_ips_from_file("nonexistent.txt");
+        let args = Args::parse_from(["reverdns", "--input", "nonexistent.txt"]);
+        let result = open_input(&args);
         assert!(result.is_err());
     }
 }
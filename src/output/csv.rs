@@ -1,10 +1,60 @@
 use crate::dns::LookupResult;
 use crate::error::Result;
+use crate::output::json::JsonMetadata;
+use crate::output::writer::ResultWriter;
+use crate::output::WriterOptions;
 use chrono::Utc;
-use csv::Writer;
-use serde::Serialize;
+use clap::ValueEnum;
+use csv::{Writer, WriterBuilder};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
 
-#[derive(Debug, Serialize)]
+/// Column names, in output order. The joined layout appends `hostnames`.
+pub const CSV_HEADER: [&str; 8] = [
+    "ip",
+    "hostname",
+    "status",
+    "ttl",
+    "latency_ms",
+    "resolver",
+    "error",
+    "timestamp",
+];
+
+/// Columns appended when forward verification is enabled
+pub const FORWARD_HEADER: [&str; 2] = ["forward_confirmed", "forward_addresses"];
+
+/// Column appended when DNSSEC validation is enabled
+pub const DNSSEC_HEADER: &str = "dnssec";
+
+/// Column appended last when caching is enabled
+pub const CACHE_HEADER: &str = "cache";
+
+/// Separator between names in the `hostnames` column, and between addresses
+/// in the `forward_addresses` column
+pub const HOSTNAME_SEPARATOR: &str = ";";
+
+/// How results with several PTR names are laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CsvHostnames {
+    /// One row per result, with every name in a trailing `hostnames` column
+    #[default]
+    Joined,
+    /// One row per name, each in the `hostname` column
+    Rows,
+}
+
+impl std::fmt::Display for CsvHostnames {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CsvHostnames::Joined => write!(f, "joined"),
+            CsvHostnames::Rows => write!(f, "rows"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct CsvRecord {
     pub ip: String,
     pub hostname: String,
@@ -14,27 +64,28 @@ pub struct CsvRecord {
     pub resolver: String,
     pub error: String,
     pub timestamp: String,
+    /// All names joined with [`HOSTNAME_SEPARATOR`]; omitted in the rows layout
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostnames: Option<String>,
+    /// `true`, `false`, or empty if there was nothing to verify; omitted
+    /// without forward verification
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forward_confirmed: Option<String>,
+    /// Addresses joined with [`HOSTNAME_SEPARATOR`]; omitted without forward
+    /// verification
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forward_addresses: Option<String>,
+    /// DNSSEC status, or empty for failed lookups; omitted without validation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dnssec: Option<String>,
+    /// `hit` or `miss`; omitted without caching
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache: Option<String>,
 }
 
-/// Format lookup results as CSV
-pub fn format_csv(results: &[LookupResult]) -> Result<String> {
-    let mut wtr = Writer::from_writer(vec![]);
-
-    // Write header
-    wtr.write_record([
-        "ip",
-        "hostname",
-        "status",
-        "ttl",
-        "latency_ms",
-        "resolver",
-        "error",
-        "timestamp",
-    ])?;
-
-    // Write records
-    for result in results {
-        let record = CsvRecord {
+impl From<&LookupResult> for CsvRecord {
+    fn from(result: &LookupResult) -> Self {
+        CsvRecord {
             ip: result.ip.clone(),
             hostname: result.hostname.clone().unwrap_or_default(),
             status: result.status.to_string(),
@@ -43,33 +94,171 @@ pub fn format_csv(results: &[LookupResult]) -> Result<String> {
             resolver: result.resolver.clone(),
             error: result.error.clone().unwrap_or_default(),
             timestamp: Utc::now().to_rfc3339(),
-        };
+            hostnames: Some(result.hostnames.join(HOSTNAME_SEPARATOR)),
+            forward_confirmed: None,
+            forward_addresses: None,
+            dnssec: None,
+            cache: None,
+        }
+    }
+}
+
+impl CsvRecord {
+    /// Rows for a result in the given layout
+    pub fn rows(result: &LookupResult, layout: CsvHostnames) -> Vec<CsvRecord> {
+        Self::rows_with(
+            result,
+            &WriterOptions {
+                csv_hostnames: layout,
+                ..WriterOptions::default()
+            },
+        )
+    }
+
+    /// Rows for a result in the layout of `options`, with the optional
+    /// columns it enables
+    pub fn rows_with(result: &LookupResult, options: &WriterOptions) -> Vec<CsvRecord> {
+        let layout = options.csv_hostnames;
+        let mut record = CsvRecord::from(result);
+        if options.verify_forward {
+            record.forward_confirmed = Some(
+                result
+                    .forward_confirmed
+                    .map(|c| c.to_string())
+                    .unwrap_or_default(),
+            );
+            record.forward_addresses = Some(result.forward_addresses.join(HOSTNAME_SEPARATOR));
+        }
+        if options.validate_dnssec {
+            record.dnssec = Some(result.dnssec.map(|s| s.to_string()).unwrap_or_default());
+        }
+        if options.cache {
+            record.cache = Some(result.cache.map(|s| s.to_string()).unwrap_or_default());
+        }
+        match layout {
+            CsvHostnames::Joined => vec![record],
+            CsvHostnames::Rows if result.hostnames.len() <= 1 => vec![CsvRecord {
+                hostnames: None,
+                ..record
+            }],
+            CsvHostnames::Rows => result
+                .hostnames
+                .iter()
+                .map(|name| CsvRecord {
+                    hostname: name.clone(),
+                    hostnames: None,
+                    ..record.clone()
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Streaming CSV writer: the header is written up front and every result is
+/// flushed as its own row
+pub struct CsvWriter<W: Write> {
+    wtr: Writer<W>,
+    options: WriterOptions,
+}
+
+impl<W: Write> CsvWriter<W> {
+    /// Create a writer in the default (joined) layout and emit the header row
+    pub fn new(out: W) -> Result<Self> {
+        Self::with_layout(out, CsvHostnames::default())
+    }
+
+    /// Create a writer for the given layout and emit the header row
+    pub fn with_layout(out: W, layout: CsvHostnames) -> Result<Self> {
+        Self::with_options(
+            out,
+            &WriterOptions {
+                csv_hostnames: layout,
+                ..WriterOptions::default()
+            },
+        )
+    }
+
+    /// Create a writer for the given options and emit the header row
+    pub fn with_options(out: W, options: &WriterOptions) -> Result<Self> {
+        let mut writer = Self::continuing(out, options);
+        writer.wtr.write_record(header(options))?;
+        Ok(writer)
+    }
+
+    /// Create a writer for the given options that adds rows to output
+    /// already holding the header row
+    pub fn continuing(out: W, options: &WriterOptions) -> Self {
+        Self {
+            wtr: WriterBuilder::new().has_headers(false).from_writer(out),
+            options: *options,
+        }
+    }
+
+    /// Flush and recover the underlying output
+    pub fn into_inner(self) -> Result<W> {
+        self.wtr.into_inner().map_err(|e| {
+            crate::error::ReverDNSError::InternalError(format!("CSV writer error: {}", e))
+        })
+    }
+}
+
+/// Header row for the given options
+fn header(options: &WriterOptions) -> Vec<&'static str> {
+    let mut header = CSV_HEADER.to_vec();
+    if options.csv_hostnames == CsvHostnames::Joined {
+        header.push("hostnames");
+    }
+    if options.verify_forward {
+        header.extend(FORWARD_HEADER);
+    }
+    if options.validate_dnssec {
+        header.push(DNSSEC_HEADER);
+    }
+    if options.cache {
+        header.push(CACHE_HEADER);
+    }
+    header
+}
+
+impl<W: Write> ResultWriter for CsvWriter<W> {
+    fn write_result(&mut self, result: &LookupResult) -> Result<()> {
+        for record in CsvRecord::rows_with(result, &self.options) {
+            self.wtr.serialize(record)?;
+        }
+        self.wtr.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self, _metadata: &JsonMetadata) -> Result<()> {
+        self.wtr.flush()?;
+        Ok(())
+    }
+}
 
-        wtr.serialize(record)?;
+/// Format lookup results as CSV
+pub fn format_csv(results: &[LookupResult]) -> Result<String> {
+    let mut writer = CsvWriter::new(vec![])?;
+
+    for result in results {
+        writer.write_result(result)?;
     }
 
-    wtr.flush()?;
-    let data = wtr.into_inner().map_err(|e| {
-        crate::error::ReverDNSError::InternalError(format!("CSV writer error: {}", e))
-    })?;
+    let data = writer.into_inner()?;
     String::from_utf8(data).map_err(crate::error::ReverDNSError::Utf8Error)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::dns::LookupStatus;
+    use crate::dns::{CacheStatus, DnssecStatus, LookupStatus};
 
     #[test]
     fn test_format_csv() {
         let results = vec![LookupResult {
-            ip: "8.8.8.8".to_string(),
-            hostname: Some("dns.google".to_string()),
-            status: LookupStatus::Success,
             ttl: Some(3600),
             latency_ms: 45,
-            resolver: "8.8.8.8".to_string(),
-            error: None,
+            ..LookupResult::new("8.8.8.8", LookupStatus::Success, "8.8.8.8")
+                .with_hostnames(["dns.google"])
         }];
 
         let csv = format_csv(&results).unwrap();
@@ -78,6 +267,127 @@ mod tests {
         assert!(csv.contains("success"));
     }
 
+    #[test]
+    fn test_multiple_hostnames() {
+        let result = LookupResult {
+            ttl: Some(300),
+            latency_ms: 5,
+            ..LookupResult::new("192.0.2.10", LookupStatus::Success, "192.0.2.53:53/udp")
+                .with_hostnames(["a.example", "b.example"])
+        };
+
+        let csv = format_csv(std::slice::from_ref(&result)).unwrap();
+        let mut lines = csv.lines();
+        assert!(lines.next().unwrap().ends_with(",timestamp,hostnames"));
+        let row = lines.next().unwrap();
+        assert!(row.starts_with("192.0.2.10,a.example,success,"));
+        assert!(row.ends_with(",a.example;b.example"));
+
+        let mut writer = CsvWriter::with_layout(Vec::new(), CsvHostnames::Rows).unwrap();
+        writer.write_result(&result).unwrap();
+        let csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], CSV_HEADER.join(","));
+        assert!(rows[1].starts_with("192.0.2.10,a.example,"));
+        assert!(rows[2].starts_with("192.0.2.10,b.example,"));
+    }
+
+    #[test]
+    fn test_forward_columns() {
+        let mut result = LookupResult {
+            forward_confirmed: Some(true),
+            forward_addresses: vec!["192.0.2.10".to_string(), "2001:db8::10".to_string()],
+            ttl: Some(300),
+            latency_ms: 5,
+            ..LookupResult::new("192.0.2.10", LookupStatus::Success, "192.0.2.53:53/udp")
+                .with_hostnames(["a.example"])
+        };
+        let options = WriterOptions {
+            csv_hostnames: CsvHostnames::Rows,
+            verify_forward: true,
+            ..WriterOptions::default()
+        };
+
+        let mut writer = CsvWriter::with_options(Vec::new(), &options).unwrap();
+        writer.write_result(&result).unwrap();
+        result.forward_confirmed = None;
+        result.forward_addresses.clear();
+        writer.write_result(&result).unwrap();
+        let csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert!(rows[0].ends_with(",timestamp,forward_confirmed,forward_addresses"));
+        assert!(rows[1].ends_with(",true,192.0.2.10;2001:db8::10"));
+        assert!(rows[2].ends_with(",,"));
+
+        // Without verification the columns are left out
+        let csv = format_csv(&[result]).unwrap();
+        assert!(!csv.contains("forward"));
+    }
+
+    #[test]
+    fn test_dnssec_column() {
+        let mut result = LookupResult {
+            dnssec: Some(DnssecStatus::Secure),
+            ttl: Some(300),
+            latency_ms: 5,
+            ..LookupResult::new("192.0.2.10", LookupStatus::Success, "192.0.2.53:53/udp")
+                .with_hostnames(["a.example"])
+        };
+        let options = WriterOptions {
+            validate_dnssec: true,
+            ..WriterOptions::default()
+        };
+
+        let mut writer = CsvWriter::with_options(Vec::new(), &options).unwrap();
+        writer.write_result(&result).unwrap();
+        result.dnssec = None;
+        writer.write_result(&result).unwrap();
+        let csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert!(rows[0].ends_with(",hostnames,dnssec"));
+        assert!(rows[1].ends_with(",a.example,secure"));
+        assert!(rows[2].ends_with(",a.example,"));
+    }
+
+    #[test]
+    fn test_cache_column() {
+        let result = LookupResult {
+            dnssec: Some(DnssecStatus::Secure),
+            cache: Some(CacheStatus::Hit),
+            ttl: Some(300),
+            attempts: 0,
+            ..LookupResult::new("192.0.2.10", LookupStatus::Success, "192.0.2.53:53/udp")
+                .with_hostnames(["a.example"])
+        };
+        let options = WriterOptions {
+            validate_dnssec: true,
+            cache: true,
+            ..WriterOptions::default()
+        };
+
+        let mut writer = CsvWriter::with_options(Vec::new(), &options).unwrap();
+        writer.write_result(&result).unwrap();
+        let csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert!(rows[0].ends_with(",dnssec,cache"));
+        assert!(rows[1].ends_with(",secure,hit"));
+
+        let csv = format_csv(&[result]).unwrap();
+        assert!(!csv.contains("hit"));
+    }
+
+    #[test]
+    fn test_continuing_omits_header() {
+        let result = LookupResult::new("192.0.2.10", LookupStatus::Success, "192.0.2.53:53/udp")
+            .with_hostnames(["a.example"]);
+        let mut writer = CsvWriter::continuing(Vec::new(), &WriterOptions::default());
+        writer.write_result(&result).unwrap();
+        let csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!(csv.lines().count(), 1);
+        assert!(csv.starts_with("192.0.2.10,a.example,success"));
+    }
+
     #[test]
     fn test_format_csv_empty() {
         let results = vec![];
@@ -0,0 +1,76 @@
+use crate::dns::{LookupResult, LookupStatus};
+use crate::error::Result;
+use crate::output::json::JsonMetadata;
+use crate::output::writer::ResultWriter;
+use std::io::Write;
+
+/// Streaming writer for `/etc/hosts` entries: each address that has names,
+/// followed by its first name and the others as aliases. Failed lookups are
+/// left out.
+pub struct HostsWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> HostsWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    /// Recover the underlying output
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write> ResultWriter for HostsWriter<W> {
+    fn write_result(&mut self, result: &LookupResult) -> Result<()> {
+        if result.status != LookupStatus::Success || result.hostnames.is_empty() {
+            return Ok(());
+        }
+        writeln!(self.out, "{}\t{}", result.ip, result.hostnames.join(" "))?;
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self, _metadata: &JsonMetadata) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::summary::RunSummary;
+
+    fn result(ip: &str, status: LookupStatus, hostnames: &[&str]) -> LookupResult {
+        LookupResult {
+            ttl: Some(300),
+            latency_ms: 5,
+            ..LookupResult::new(ip, status, "192.0.2.53:53/udp").with_hostnames(hostnames.to_vec())
+        }
+    }
+
+    #[test]
+    fn test_hosts_entries() {
+        let mut writer = HostsWriter::new(Vec::new());
+        for result in [
+            result(
+                "192.0.2.1",
+                LookupStatus::Success,
+                &["a.example", "b.example"],
+            ),
+            result("192.0.2.2", LookupStatus::NxDomain, &[]),
+            result("2001:db8::1", LookupStatus::Success, &["c.example"]),
+        ] {
+            writer.write_result(&result).unwrap();
+        }
+        writer.finish(&RunSummary::new().to_metadata(0)).unwrap();
+
+        let text = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(
+            text,
+            "192.0.2.1\ta.example b.example\n2001:db8::1\tc.example\n"
+        );
+    }
+}
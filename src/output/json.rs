@@ -1,76 +1,141 @@
-use crate::dns::LookupResult;
+use crate::dns::{CacheStatus, DnssecStatus, LookupResult, UpstreamHealth};
 use crate::error::Result;
+use crate::output::summary::RunSummary;
+use crate::output::writer::ResultWriter;
 use serde::{Deserialize, Serialize};
 use chrono::Utc;
+use std::io::Write;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JsonOutput {
     pub results: Vec<JsonResult>,
     pub metadata: JsonMetadata,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JsonResult {
     pub ip: String,
     pub hostname: Option<String>,
+    #[serde(default)]
+    pub hostnames: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forward_confirmed: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forward_addresses: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dnssec: Option<DnssecStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<CacheStatus>,
     pub status: String,
     pub ttl: Option<u32>,
     pub latency_ms: u128,
+    #[serde(default)]
+    pub attempts: u32,
     pub resolver: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
     pub timestamp: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JsonMetadata {
     pub total_lookups: usize,
     pub successful: usize,
     pub failed: usize,
     pub total_time_ms: u128,
     pub average_latency_ms: f64,
+    /// Lookups served from the cache, when caching is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_hits: Option<usize>,
+    /// Lookups that had to query a resolver, when caching is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_misses: Option<usize>,
+    /// Per-resolver health at the end of the run
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resolvers: Vec<UpstreamHealth>,
 }
 
-/// Format lookup results as JSON
-pub fn format_json(results: &[LookupResult], total_time_ms: u128) -> Result<String> {
-    use crate::dns::LookupStatus;
-
-    let successful = results.iter().filter(|r| r.status == LookupStatus::Success).count();
-    let failed = results.len() - successful;
-    let average_latency = if results.is_empty() {
-        0.0
-    } else {
-        results.iter().map(|r| r.latency_ms as f64).sum::<f64>() / results.len() as f64
-    };
-
-    let json_results: Vec<JsonResult> = results
-        .iter()
-        .map(|r| JsonResult {
+impl From<&LookupResult> for JsonResult {
+    fn from(r: &LookupResult) -> Self {
+        JsonResult {
             ip: r.ip.clone(),
             hostname: r.hostname.clone(),
+            hostnames: r.hostnames.clone(),
+            forward_confirmed: r.forward_confirmed,
+            forward_addresses: r.forward_addresses.clone(),
+            dnssec: r.dnssec,
+            cache: r.cache,
             status: r.status.to_string(),
             ttl: r.ttl,
             latency_ms: r.latency_ms,
+            attempts: r.attempts,
             resolver: r.resolver.clone(),
             error: r.error.clone(),
             timestamp: Utc::now().to_rfc3339(),
-        })
-        .collect();
-
-    let output = JsonOutput {
-        results: json_results,
-        metadata: JsonMetadata {
-            total_lookups: results.len(),
-            successful,
-            failed,
-            total_time_ms,
-            average_latency_ms: average_latency,
-        },
-    };
+        }
+    }
+}
+
+/// Build the JSON document for a set of lookup results
+pub fn build_json_output(results: &[LookupResult], total_time_ms: u128) -> JsonOutput {
+    JsonOutput {
+        results: results.iter().map(JsonResult::from).collect(),
+        metadata: RunSummary::from_results(results).to_metadata(total_time_ms),
+    }
+}
 
+/// Format lookup results as JSON
+pub fn format_json(results: &[LookupResult], total_time_ms: u128) -> Result<String> {
+    let output = build_json_output(results, total_time_ms);
     Ok(serde_json::to_string_pretty(&output)?)
 }
 
+/// Streaming writer for the JSON document format.
+///
+/// Produces the same document as [`format_json`], writing each result as it
+/// arrives and appending `metadata` after the results array on `finish`.
+pub struct JsonWriter<W: Write> {
+    out: W,
+    written: usize,
+}
+
+impl<W: Write> JsonWriter<W> {
+    /// Start the document
+    pub fn new(mut out: W) -> Result<Self> {
+        out.write_all(b"{\n  \"results\": [")?;
+        Ok(Self { out, written: 0 })
+    }
+
+    /// Recover the underlying output
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write> ResultWriter for JsonWriter<W> {
+    fn write_result(&mut self, result: &LookupResult) -> Result<()> {
+        let item = serde_json::to_string_pretty(&JsonResult::from(result))?;
+        let separator = if self.written == 0 { "\n" } else { ",\n" };
+        write!(self.out, "{}    {}", separator, item.replace('\n', "\n    "))?;
+        self.out.flush()?;
+        self.written += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self, metadata: &JsonMetadata) -> Result<()> {
+        let metadata = serde_json::to_string_pretty(metadata)?;
+        let close = if self.written == 0 { "]" } else { "\n  ]" };
+        write!(
+            self.out,
+            "{},\n  \"metadata\": {}\n}}\n",
+            close,
+            metadata.replace('\n', "\n  ")
+        )?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -80,13 +145,10 @@ mod tests {
     fn test_format_json() {
         let results = vec![
             LookupResult {
-                ip: "8.8.8.8".to_string(),
-                hostname: Some("dns.google".to_string()),
-                status: LookupStatus::Success,
                 ttl: Some(3600),
                 latency_ms: 45,
-                resolver: "8.8.8.8".to_string(),
-                error: None,
+                ..LookupResult::new("8.8.8.8", LookupStatus::Success, "8.8.8.8")
+                    .with_hostnames(["dns.google"])
             },
         ];
 
@@ -94,6 +156,58 @@ mod tests {
         assert!(json.contains("8.8.8.8"));
         assert!(json.contains("dns.google"));
         assert!(json.contains("success"));
+        assert!(!json.contains("forward_confirmed"));
+        assert!(!json.contains("dnssec"));
+    }
+
+    #[test]
+    fn test_json_result_verification_fields() {
+        let result = LookupResult {
+            forward_confirmed: Some(true),
+            forward_addresses: vec!["8.8.8.8".to_string()],
+            dnssec: Some(DnssecStatus::Insecure),
+            ttl: Some(3600),
+            latency_ms: 45,
+            ..LookupResult::new("8.8.8.8", LookupStatus::Success, "8.8.8.8")
+                .with_hostnames(["dns.google"])
+        };
+
+        let value = serde_json::to_value(JsonResult::from(&result)).unwrap();
+        assert_eq!(value["forward_confirmed"], true);
+        assert_eq!(value["forward_addresses"][0], "8.8.8.8");
+        assert_eq!(value["dnssec"], "insecure");
+    }
+
+    #[test]
+    fn test_json_writer_streams_document() {
+        let result = LookupResult {
+            ttl: Some(3600),
+            latency_ms: 45,
+            ..LookupResult::new("8.8.8.8", LookupStatus::Success, "8.8.8.8")
+                .with_hostnames(["dns.google"])
+        };
+
+        let mut writer = JsonWriter::new(Vec::new()).unwrap();
+        writer.write_result(&result).unwrap();
+        writer.write_result(&result).unwrap();
+        let summary = RunSummary::from_results(&[result.clone(), result]);
+        writer.finish(&summary.to_metadata(10)).unwrap();
+
+        let text = String::from_utf8(writer.into_inner()).unwrap();
+        let parsed: JsonOutput = serde_json::from_str(&text).unwrap();
+        assert_eq!(parsed.results.len(), 2);
+        assert_eq!(parsed.results[0].hostnames, vec!["dns.google"]);
+        assert_eq!(parsed.metadata.total_lookups, 2);
+        assert_eq!(parsed.metadata.total_time_ms, 10);
+    }
+
+    #[test]
+    fn test_json_writer_matches_format_json_layout() {
+        let mut writer = JsonWriter::new(Vec::new()).unwrap();
+        writer.finish(&RunSummary::new().to_metadata(0)).unwrap();
+        let streamed = String::from_utf8(writer.into_inner()).unwrap();
+
+        assert_eq!(streamed.trim_end(), format_json(&[], 0).unwrap());
     }
 
     #[test]
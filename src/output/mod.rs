@@ -1,5 +1,172 @@
 pub mod json;
 pub mod csv;
+pub mod hosts;
+pub mod ndjson;
+pub mod sqlite;
+pub mod summary;
+pub mod text;
+pub mod writer;
+pub mod zone;
 
-pub use json::format_json;
-pub use csv::format_csv;
+pub use json::{build_json_output, format_json, JsonWriter};
+pub use csv::{format_csv, CsvHostnames, CsvWriter};
+pub use hosts::HostsWriter;
+pub use ndjson::NdjsonWriter;
+pub use sqlite::SqliteWriter;
+pub use summary::RunSummary;
+pub use text::TextWriter;
+pub use writer::ResultWriter;
+pub use zone::ZoneWriter;
+
+use crate::cli::OutputFormat;
+use crate::error::{Result, ReverDNSError};
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Format-specific output settings
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WriterOptions {
+    /// Layout of CSV rows for results with several names
+    pub csv_hostnames: CsvHostnames,
+    /// Add the forward verification columns to CSV rows
+    pub verify_forward: bool,
+    /// Add the DNSSEC status column to CSV rows
+    pub validate_dnssec: bool,
+    /// Add the cache hit/miss column to CSV rows
+    pub cache: bool,
+    /// End NDJSON output with a line holding the run metadata
+    pub ndjson_summary: bool,
+}
+
+/// Create the streaming writer for an output format
+pub fn create_writer<W>(
+    format: OutputFormat,
+    out: W,
+    options: &WriterOptions,
+) -> Result<Box<dyn ResultWriter + Send>>
+where
+    W: Write + Send + 'static,
+{
+    Ok(match format {
+        OutputFormat::Json => Box::new(JsonWriter::new(out)?),
+        OutputFormat::Csv => Box::new(CsvWriter::with_options(out, options)?),
+        OutputFormat::Ndjson => Box::new(NdjsonWriter::with_options(out, options)),
+        OutputFormat::Hosts => Box::new(HostsWriter::new(out)),
+        OutputFormat::Zone => Box::new(ZoneWriter::new(out)),
+        OutputFormat::Text => Box::new(TextWriter::new(out)),
+        OutputFormat::Sqlite => {
+            return Err(ReverDNSError::InvalidFormat(
+                "sqlite output must be written to a file (--output)".to_string(),
+            ))
+        }
+    })
+}
+
+/// Whether a resumed run adds its results to the output file the
+/// interrupted run left behind. JSON documents and zone files are only valid
+/// whole, so for them the earlier results are written again into a new file.
+pub fn appends_on_resume(format: OutputFormat) -> bool {
+    !matches!(format, OutputFormat::Json | OutputFormat::Zone)
+}
+
+/// Create the writer for a resumed run's output file at `path`, continuing
+/// the file where [`appends_on_resume`] allows and replacing it otherwise
+pub fn resume_file_writer(
+    format: OutputFormat,
+    path: &Path,
+    options: &WriterOptions,
+) -> Result<Box<dyn ResultWriter + Send>> {
+    match format {
+        _ if !appends_on_resume(format) => create_file_writer(format, path, options),
+        OutputFormat::Sqlite => Ok(Box::new(SqliteWriter::resume(path)?)),
+        _ => {
+            let file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+            let has_header = file.metadata()?.len() > 0;
+            let out = BufWriter::new(file);
+            if format == OutputFormat::Csv && has_header {
+                Ok(Box::new(CsvWriter::continuing(out, options)))
+            } else {
+                create_writer(format, out, options)
+            }
+        }
+    }
+}
+
+/// Create the writer for an output format that writes to the file at `path`
+pub fn create_file_writer(
+    format: OutputFormat,
+    path: &Path,
+    options: &WriterOptions,
+) -> Result<Box<dyn ResultWriter + Send>> {
+    match format {
+        OutputFormat::Sqlite => Ok(Box::new(SqliteWriter::open(path)?)),
+        _ => create_writer(format, BufWriter::new(fs::File::create(path)?), options),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::{LookupResult, LookupStatus};
+    use crate::output::summary::RunSummary;
+    use tempfile::TempDir;
+
+    fn write(mut writer: Box<dyn ResultWriter + Send>, ip: &str) {
+        let result = LookupResult::new(ip, LookupStatus::Success, "192.0.2.53:53/udp")
+            .with_hostnames(["a.example"]);
+        writer.write_result(&result).unwrap();
+        writer.finish(&RunSummary::new().to_metadata(0)).unwrap();
+    }
+
+    #[test]
+    fn test_resume_appends_csv() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("results.csv");
+        let options = WriterOptions::default();
+        write(
+            create_file_writer(OutputFormat::Csv, &path, &options).unwrap(),
+            "192.0.2.1",
+        );
+        write(
+            resume_file_writer(OutputFormat::Csv, &path, &options).unwrap(),
+            "192.0.2.2",
+        );
+
+        let csv = fs::read_to_string(&path).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows.len(), 3);
+        assert!(rows[0].starts_with("ip,"));
+        assert!(rows[1].starts_with("192.0.2.1,"));
+        assert!(rows[2].starts_with("192.0.2.2,"));
+
+        // Without earlier output the header is still written
+        let path = dir.path().join("missing.csv");
+        write(
+            resume_file_writer(OutputFormat::Csv, &path, &options).unwrap(),
+            "192.0.2.3",
+        );
+        assert!(fs::read_to_string(&path).unwrap().starts_with("ip,"));
+    }
+
+    #[test]
+    fn test_resume_replaces_json() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("results.json");
+        let options = WriterOptions::default();
+        write(
+            create_file_writer(OutputFormat::Json, &path, &options).unwrap(),
+            "192.0.2.1",
+        );
+        write(
+            resume_file_writer(OutputFormat::Json, &path, &options).unwrap(),
+            "192.0.2.2",
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["results"].as_array().unwrap().len(), 1);
+        assert!(!appends_on_resume(OutputFormat::Zone));
+        assert!(appends_on_resume(OutputFormat::Ndjson));
+    }
+}
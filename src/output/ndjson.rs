@@ -0,0 +1,108 @@
+use crate::dns::LookupResult;
+use crate::error::Result;
+use crate::output::json::{JsonMetadata, JsonResult};
+use crate::output::writer::ResultWriter;
+use crate::output::WriterOptions;
+use serde::Serialize;
+use std::io::Write;
+
+/// Streaming newline-delimited JSON writer: one compact [`JsonResult`] per
+/// line, optionally followed by a `{"metadata": ...}` line
+pub struct NdjsonWriter<W: Write> {
+    out: W,
+    summary: bool,
+}
+
+/// Trailing line holding the run metadata
+#[derive(Serialize)]
+struct SummaryLine<'a> {
+    metadata: &'a JsonMetadata,
+}
+
+impl<W: Write> NdjsonWriter<W> {
+    /// Create a writer that emits results only
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            summary: false,
+        }
+    }
+
+    /// Create a writer that also ends with a summary line if the options ask
+    /// for one
+    pub fn with_options(out: W, options: &WriterOptions) -> Self {
+        Self {
+            out,
+            summary: options.ndjson_summary,
+        }
+    }
+
+    /// Recover the underlying output
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write> ResultWriter for NdjsonWriter<W> {
+    fn write_result(&mut self, result: &LookupResult) -> Result<()> {
+        serde_json::to_writer(&mut self.out, &JsonResult::from(result))?;
+        self.out.write_all(b"\n")?;
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self, metadata: &JsonMetadata) -> Result<()> {
+        if self.summary {
+            serde_json::to_writer(&mut self.out, &SummaryLine { metadata })?;
+            self.out.write_all(b"\n")?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::LookupStatus;
+    use crate::output::summary::RunSummary;
+
+    #[test]
+    fn test_ndjson_one_line_per_result() {
+        let result = LookupResult {
+            latency_ms: 12,
+            ..LookupResult::new("8.8.8.8", LookupStatus::Success, "8.8.8.8")
+                .with_hostnames(["dns.google"])
+        };
+
+        let mut writer = NdjsonWriter::new(Vec::new());
+        writer.write_result(&result).unwrap();
+        writer.write_result(&result).unwrap();
+        writer.finish(&RunSummary::new().to_metadata(0)).unwrap();
+
+        let text = String::from_utf8(writer.into_inner()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let parsed: JsonResult = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(parsed.hostname.as_deref(), Some("dns.google"));
+
+        // With a summary, the metadata follows the results on its own line
+        let options = WriterOptions {
+            ndjson_summary: true,
+            ..WriterOptions::default()
+        };
+        let mut writer = NdjsonWriter::with_options(Vec::new(), &options);
+        let mut summary = RunSummary::new();
+        writer.write_result(&result).unwrap();
+        summary.record(&result);
+        writer.finish(&summary.to_metadata(15)).unwrap();
+
+        let text = String::from_utf8(writer.into_inner()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("{\"ip\":\"8.8.8.8\""));
+        let trailer: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(trailer["metadata"]["total_lookups"], 1);
+        assert_eq!(trailer["metadata"]["total_time_ms"], 15);
+    }
+}
@@ -0,0 +1,386 @@
+use crate::dns::LookupResult;
+use crate::error::{Result, ReverDNSError};
+use crate::output::json::{JsonMetadata, JsonResult};
+use crate::output::writer::ResultWriter;
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use uuid::Uuid;
+
+/// Number of results written per transaction
+pub const BATCH_SIZE: usize = 500;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS runs (
+        id TEXT PRIMARY KEY NOT NULL,
+        started_at TEXT NOT NULL,
+        finished_at TEXT,
+        total_lookups INTEGER,
+        successful INTEGER,
+        failed INTEGER,
+        total_time_ms INTEGER,
+        metadata TEXT
+    );
+    CREATE TABLE IF NOT EXISTS results (
+        ip TEXT PRIMARY KEY NOT NULL,
+        hostname TEXT,
+        hostnames TEXT NOT NULL,
+        status TEXT NOT NULL,
+        ttl INTEGER,
+        latency_ms INTEGER NOT NULL,
+        attempts INTEGER NOT NULL,
+        resolver TEXT NOT NULL,
+        error TEXT,
+        forward_confirmed INTEGER,
+        forward_addresses TEXT,
+        dnssec TEXT,
+        cache TEXT,
+        timestamp TEXT NOT NULL,
+        run_id TEXT NOT NULL REFERENCES runs (id)
+    );
+    CREATE INDEX IF NOT EXISTS results_run_id ON results (run_id);
+    CREATE INDEX IF NOT EXISTS results_status ON results (status);
+";
+
+const UPSERT: &str = "
+    INSERT INTO results (ip, hostname, hostnames, status, ttl, latency_ms, attempts, resolver,
+                         error, forward_confirmed, forward_addresses, dnssec, cache, timestamp,
+                         run_id)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
+    ON CONFLICT (ip) DO UPDATE SET
+        hostname = excluded.hostname,
+        hostnames = excluded.hostnames,
+        status = excluded.status,
+        ttl = excluded.ttl,
+        latency_ms = excluded.latency_ms,
+        attempts = excluded.attempts,
+        resolver = excluded.resolver,
+        error = excluded.error,
+        forward_confirmed = excluded.forward_confirmed,
+        forward_addresses = excluded.forward_addresses,
+        dnssec = excluded.dnssec,
+        cache = excluded.cache,
+        timestamp = excluded.timestamp,
+        run_id = excluded.run_id
+";
+
+/// Writer storing results in an SQLite database.
+///
+/// The `results` table holds the latest result for each address: a later
+/// run over the same database replaces the rows of the addresses it looks
+/// up again and leaves the others alone. Each row names the run that wrote
+/// it, and every invocation adds a row to `runs` whose metadata is filled
+/// in by `finish`. `hostnames` and `forward_addresses` are JSON arrays.
+///
+/// Results are committed in transactions of [`BATCH_SIZE`] rows, so up to
+/// that many are only buffered until the next batch or `finish`.
+pub struct SqliteWriter {
+    conn: Connection,
+    run_id: String,
+    pending: Vec<JsonResult>,
+}
+
+impl SqliteWriter {
+    /// Open or create the database at `path` and record the start of a run
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::with_connection(connect(path)?)
+    }
+
+    /// Open the database at `path` to continue its latest run, as when an
+    /// interrupted run is resumed; without any run yet, one is started
+    pub fn resume(path: &Path) -> Result<Self> {
+        let conn = connect(path)?;
+        conn.execute_batch(SCHEMA).map_err(db_error)?;
+        let latest = conn
+            .query_row(
+                "SELECT id FROM runs ORDER BY started_at DESC, rowid DESC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db_error)?;
+        match latest {
+            Some(run_id) => Ok(Self {
+                conn,
+                run_id,
+                pending: Vec::with_capacity(BATCH_SIZE),
+            }),
+            None => Self::with_connection(conn),
+        }
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self> {
+        let run_id = Uuid::new_v4().to_string();
+        conn.execute_batch(SCHEMA)
+            .and_then(|_| {
+                conn.execute(
+                    "INSERT INTO runs (id, started_at) VALUES (?1, ?2)",
+                    params![run_id, Utc::now().to_rfc3339()],
+                )
+            })
+            .map_err(db_error)?;
+        Ok(Self {
+            conn,
+            run_id,
+            pending: Vec::with_capacity(BATCH_SIZE),
+        })
+    }
+
+    /// Identifier of this run in the `runs` table
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Commit the buffered results in one transaction
+    pub fn flush(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let tx = self.conn.transaction().map_err(db_error)?;
+        {
+            let mut upsert = tx.prepare_cached(UPSERT).map_err(db_error)?;
+            for result in &self.pending {
+                upsert
+                    .execute(params![
+                        result.ip,
+                        result.hostname,
+                        serde_json::to_string(&result.hostnames)?,
+                        result.status,
+                        result.ttl,
+                        i64::try_from(result.latency_ms).unwrap_or(i64::MAX),
+                        result.attempts,
+                        result.resolver,
+                        result.error,
+                        result.forward_confirmed,
+                        (!result.forward_addresses.is_empty())
+                            .then(|| serde_json::to_string(&result.forward_addresses))
+                            .transpose()?,
+                        result.dnssec.map(|s| s.to_string()),
+                        result.cache.map(|s| s.to_string()),
+                        result.timestamp,
+                        self.run_id,
+                    ])
+                    .map_err(db_error)?;
+            }
+        }
+        tx.commit().map_err(db_error)?;
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Recover the underlying connection, committing any buffered results
+    pub fn into_inner(mut self) -> Result<Connection> {
+        self.flush()?;
+        Ok(self.conn)
+    }
+}
+
+impl ResultWriter for SqliteWriter {
+    fn write_result(&mut self, result: &LookupResult) -> Result<()> {
+        self.pending.push(JsonResult::from(result));
+        if self.pending.len() >= BATCH_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, metadata: &JsonMetadata) -> Result<()> {
+        self.flush()?;
+        self.conn
+            .execute(
+                "UPDATE runs SET finished_at = ?2, total_lookups = ?3, successful = ?4,
+                     failed = ?5, total_time_ms = ?6, metadata = ?7
+                 WHERE id = ?1",
+                params![
+                    self.run_id,
+                    Utc::now().to_rfc3339(),
+                    metadata.total_lookups,
+                    metadata.successful,
+                    metadata.failed,
+                    i64::try_from(metadata.total_time_ms).unwrap_or(i64::MAX),
+                    serde_json::to_string(metadata)?,
+                ],
+            )
+            .map_err(db_error)?;
+        Ok(())
+    }
+}
+
+fn connect(path: &Path) -> Result<Connection> {
+    Connection::open(path).map_err(|e| {
+        ReverDNSError::ConfigError(format!(
+            "Cannot open SQLite output {}: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+fn db_error(e: rusqlite::Error) -> ReverDNSError {
+    ReverDNSError::InternalError(format!("SQLite output error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::LookupStatus;
+    use crate::output::summary::RunSummary;
+    use tempfile::TempDir;
+
+    fn result(ip: &str, hostnames: &[&str]) -> LookupResult {
+        let status = if hostnames.is_empty() {
+            LookupStatus::NxDomain
+        } else {
+            LookupStatus::Success
+        };
+        LookupResult {
+            ttl: Some(300),
+            latency_ms: 5,
+            ..LookupResult::new(ip, status, "192.0.2.53:53/udp").with_hostnames(hostnames.to_vec())
+        }
+    }
+
+    fn run(path: &Path, results: &[LookupResult]) -> String {
+        let mut writer = SqliteWriter::open(path).unwrap();
+        let mut summary = RunSummary::new();
+        for result in results {
+            writer.write_result(result).unwrap();
+            summary.record(result);
+        }
+        writer.finish(&summary.to_metadata(10)).unwrap();
+        writer.run_id().to_string()
+    }
+
+    #[test]
+    fn test_results_and_runs() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("results.db");
+        let run_id = run(
+            &path,
+            &[
+                result("192.0.2.1", &["a.example", "b.example"]),
+                result("192.0.2.2", &[]),
+            ],
+        );
+
+        let conn = Connection::open(&path).unwrap();
+        let (hostname, hostnames, status, row_run): (String, String, String, String) = conn
+            .query_row(
+                "SELECT hostname, hostnames, status, run_id FROM results WHERE ip = '192.0.2.1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!(hostname, "a.example");
+        assert_eq!(hostnames, r#"["a.example","b.example"]"#);
+        assert_eq!(status, "success");
+        assert_eq!(row_run, run_id);
+
+        let (total, metadata): (i64, String) = conn
+            .query_row(
+                "SELECT total_lookups, metadata FROM runs WHERE id = ?1",
+                [&run_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(total, 2);
+        let metadata: JsonMetadata = serde_json::from_str(&metadata).unwrap();
+        assert_eq!(metadata.failed, 1);
+    }
+
+    #[test]
+    fn test_rerun_upserts() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("results.db");
+        let first = run(
+            &path,
+            &[
+                result("192.0.2.1", &["a.example"]),
+                result("192.0.2.2", &["b.example"]),
+            ],
+        );
+        let second = run(&path, &[result("192.0.2.1", &["c.example"])]);
+
+        let conn = Connection::open(&path).unwrap();
+        let rows: Vec<(String, String, String)> = conn
+            .prepare("SELECT ip, hostname, run_id FROM results ORDER BY ip")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                ("192.0.2.1".to_string(), "c.example".to_string(), second),
+                ("192.0.2.2".to_string(), "b.example".to_string(), first),
+            ]
+        );
+        let runs: i64 = conn
+            .query_row("SELECT COUNT(*) FROM runs", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(runs, 2);
+    }
+
+    #[test]
+    fn test_resume_continues_latest_run() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("results.db");
+        let first = run(&path, &[result("192.0.2.1", &["a.example"])]);
+
+        let mut writer = SqliteWriter::resume(&path).unwrap();
+        assert_eq!(writer.run_id(), first);
+        let mut summary = RunSummary::new();
+        for result in [
+            result("192.0.2.1", &["a.example"]),
+            result("192.0.2.2", &["b.example"]),
+        ] {
+            // Results of the interrupted run are counted, not written again
+            if result.ip != "192.0.2.1" {
+                writer.write_result(&result).unwrap();
+            }
+            summary.record(&result);
+        }
+        writer.finish(&summary.to_metadata(10)).unwrap();
+
+        let conn = Connection::open(&path).unwrap();
+        let (runs, total): (i64, i64) = conn
+            .query_row("SELECT COUNT(*), MAX(total_lookups) FROM runs", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!((runs, total), (1, 2));
+        let results: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM results WHERE run_id = ?1",
+                [&first],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(results, 2);
+
+        // A database without runs starts one
+        let empty = dir.path().join("empty.db");
+        assert!(!SqliteWriter::resume(&empty).unwrap().run_id().is_empty());
+    }
+
+    #[test]
+    fn test_batches() {
+        let mut writer =
+            SqliteWriter::with_connection(Connection::open_in_memory().unwrap()).unwrap();
+        for i in 0..BATCH_SIZE + 1 {
+            let ip = format!("10.0.{}.{}", i / 256, i % 256);
+            writer.write_result(&result(&ip, &["h.example"])).unwrap();
+        }
+        let count = |conn: &Connection| -> usize {
+            conn.query_row("SELECT COUNT(*) FROM results", [], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(count(&writer.conn), BATCH_SIZE);
+        assert_eq!(writer.pending.len(), 1);
+
+        let conn = writer.into_inner().unwrap();
+        assert_eq!(count(&conn), BATCH_SIZE + 1);
+    }
+}
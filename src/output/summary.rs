@@ -0,0 +1,164 @@
+use crate::dns::{CacheStatus, LookupResult, LookupStatus};
+use crate::output::json::JsonMetadata;
+
+/// Running totals over a stream of lookup results
+#[derive(Debug, Clone, Default)]
+pub struct RunSummary {
+    pub total: usize,
+    pub successful: usize,
+    pub failed: usize,
+    pub cache_hits: usize,
+    pub cache_misses: usize,
+    latency_total_ms: u128,
+}
+
+impl RunSummary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build a summary over a finished set of results
+    pub fn from_results(results: &[LookupResult]) -> Self {
+        let mut summary = Self::new();
+        for result in results {
+            summary.record(result);
+        }
+        summary
+    }
+
+    /// Account for one more result
+    pub fn record(&mut self, result: &LookupResult) {
+        self.total += 1;
+        if result.status == LookupStatus::Success {
+            self.successful += 1;
+        } else {
+            self.failed += 1;
+        }
+        match result.cache {
+            Some(CacheStatus::Hit) => self.cache_hits += 1,
+            Some(CacheStatus::Miss) => self.cache_misses += 1,
+            None => {}
+        }
+        self.latency_total_ms += result.latency_ms;
+    }
+
+    /// Add the totals of another summary to this one
+    pub fn merge(&mut self, other: &RunSummary) {
+        self.total += other.total;
+        self.successful += other.successful;
+        self.failed += other.failed;
+        self.cache_hits += other.cache_hits;
+        self.cache_misses += other.cache_misses;
+        self.latency_total_ms += other.latency_total_ms;
+    }
+
+    /// Mean lookup latency in milliseconds
+    pub fn average_latency_ms(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.latency_total_ms as f64 / self.total as f64
+        }
+    }
+
+    /// Percentage of successful lookups
+    pub fn success_rate(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            (self.successful as f64 / self.total as f64) * 100.0
+        }
+    }
+
+    /// Whether any result went through the cache
+    pub fn cached(&self) -> bool {
+        self.cache_hits + self.cache_misses > 0
+    }
+
+    /// Percentage of cached lookups served from the cache
+    pub fn cache_hit_rate(&self) -> f64 {
+        if self.cached() {
+            (self.cache_hits as f64 / (self.cache_hits + self.cache_misses) as f64) * 100.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Metadata block for JSON output
+    pub fn to_metadata(&self, total_time_ms: u128) -> JsonMetadata {
+        JsonMetadata {
+            total_lookups: self.total,
+            successful: self.successful,
+            failed: self.failed,
+            total_time_ms,
+            average_latency_ms: self.average_latency_ms(),
+            cache_hits: self.cached().then_some(self.cache_hits),
+            cache_misses: self.cached().then_some(self.cache_misses),
+            resolvers: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(status: LookupStatus, latency_ms: u128) -> LookupResult {
+        LookupResult {
+            latency_ms,
+            ..LookupResult::new("192.0.2.1", status, "test")
+        }
+    }
+
+    #[test]
+    fn test_summary_totals() {
+        let summary = RunSummary::from_results(&[
+            result(LookupStatus::Success, 10),
+            result(LookupStatus::Failed, 30),
+        ]);
+
+        assert_eq!(summary.total, 2);
+        assert_eq!(summary.successful, 1);
+        assert_eq!(summary.failed, 1);
+        assert_eq!(summary.average_latency_ms(), 20.0);
+        assert_eq!(summary.success_rate(), 50.0);
+        assert_eq!(summary.to_metadata(5).total_time_ms, 5);
+    }
+
+    #[test]
+    fn test_summary_merge() {
+        let mut summary = RunSummary::from_results(&[result(LookupStatus::Success, 10)]);
+        summary.merge(&RunSummary::from_results(&[result(LookupStatus::Timeout, 50)]));
+        assert_eq!(summary.total, 2);
+        assert_eq!(summary.failed, 1);
+        assert_eq!(summary.average_latency_ms(), 30.0);
+    }
+
+    #[test]
+    fn test_cache_counts() {
+        let uncached = RunSummary::from_results(&[result(LookupStatus::Success, 10)]);
+        assert_eq!(uncached.to_metadata(0).cache_hits, None);
+
+        let cached = |cache| LookupResult {
+            cache: Some(cache),
+            ..result(LookupStatus::Success, 10)
+        };
+        let summary = RunSummary::from_results(&[
+            cached(CacheStatus::Miss),
+            cached(CacheStatus::Hit),
+            cached(CacheStatus::Hit),
+            cached(CacheStatus::Hit),
+        ]);
+        assert_eq!(summary.cache_hit_rate(), 75.0);
+        let metadata = summary.to_metadata(0);
+        assert_eq!(metadata.cache_hits, Some(3));
+        assert_eq!(metadata.cache_misses, Some(1));
+    }
+
+    #[test]
+    fn test_empty_summary() {
+        let summary = RunSummary::new();
+        assert_eq!(summary.average_latency_ms(), 0.0);
+        assert_eq!(summary.success_rate(), 0.0);
+    }
+}
@@ -0,0 +1,67 @@
+use crate::dns::{LookupResult, LookupStatus};
+use crate::error::Result;
+use crate::output::json::JsonMetadata;
+use crate::output::writer::ResultWriter;
+use std::io::Write;
+
+/// Streaming plain-text writer: an `ip<TAB>hostname` line for every name,
+/// so an address with several names gets several lines. Failed lookups are
+/// left out.
+pub struct TextWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> TextWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    /// Recover the underlying output
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write> ResultWriter for TextWriter<W> {
+    fn write_result(&mut self, result: &LookupResult) -> Result<()> {
+        if result.status != LookupStatus::Success {
+            return Ok(());
+        }
+        for name in &result.hostnames {
+            writeln!(self.out, "{}\t{}", result.ip, name)?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self, _metadata: &JsonMetadata) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::summary::RunSummary;
+
+    #[test]
+    fn test_one_line_per_name() {
+        let mut result = LookupResult {
+            ttl: Some(300),
+            latency_ms: 5,
+            ..LookupResult::new("192.0.2.1", LookupStatus::Success, "192.0.2.53:53/udp")
+                .with_hostnames(["a.example", "b.example"])
+        };
+
+        let mut writer = TextWriter::new(Vec::new());
+        writer.write_result(&result).unwrap();
+        result.ip = "192.0.2.2".to_string();
+        result.status = LookupStatus::Timeout;
+        writer.write_result(&result).unwrap();
+        writer.finish(&RunSummary::new().to_metadata(0)).unwrap();
+
+        let text = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(text, "192.0.2.1\ta.example\n192.0.2.1\tb.example\n");
+    }
+}
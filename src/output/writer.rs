@@ -0,0 +1,25 @@
+use crate::dns::LookupResult;
+use crate::error::Result;
+use crate::output::json::JsonMetadata;
+
+/// Incremental sink for lookup results.
+///
+/// Results are written as they arrive so memory use does not grow with the
+/// size of the input; `finish` completes the document once the run is over.
+pub trait ResultWriter {
+    /// Write (and flush) a single result
+    fn write_result(&mut self, result: &LookupResult) -> Result<()>;
+
+    /// Write any trailer and flush the underlying output
+    fn finish(&mut self, metadata: &JsonMetadata) -> Result<()>;
+}
+
+impl<T: ResultWriter + ?Sized> ResultWriter for Box<T> {
+    fn write_result(&mut self, result: &LookupResult) -> Result<()> {
+        (**self).write_result(result)
+    }
+
+    fn finish(&mut self, metadata: &JsonMetadata) -> Result<()> {
+        (**self).finish(metadata)
+    }
+}
@@ -0,0 +1,201 @@
+use crate::dns::{LookupResult, LookupStatus};
+use crate::error::Result;
+use crate::output::json::JsonMetadata;
+use crate::output::writer::ResultWriter;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// `$TTL` of a zone none of whose answers carried a TTL
+pub const DEFAULT_ZONE_TTL: u32 = 3600;
+
+/// Writer for BIND reverse-zone snippets.
+///
+/// Addresses are grouped by reverse zone (the /24 for IPv4, the /64 for
+/// IPv6) and each zone is written as a `$ORIGIN` and `$TTL` followed by its
+/// PTR records in address order. The `$TTL` is the lowest TTL of the zone's
+/// answers; records with a longer one carry it explicitly. Since zones are
+/// only complete once every address is known, the whole output is written
+/// by `finish`. Failed lookups are left out.
+pub struct ZoneWriter<W: Write> {
+    out: W,
+    zones: BTreeMap<IpAddr, Zone>,
+}
+
+#[derive(Default)]
+struct Zone {
+    origin: String,
+    records: BTreeMap<IpAddr, Record>,
+}
+
+struct Record {
+    owner: String,
+    names: Vec<String>,
+    ttl: Option<u32>,
+}
+
+impl Zone {
+    fn ttl(&self) -> u32 {
+        self.records
+            .values()
+            .filter_map(|r| r.ttl)
+            .min()
+            .unwrap_or(DEFAULT_ZONE_TTL)
+    }
+}
+
+impl<W: Write> ZoneWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            zones: BTreeMap::new(),
+        }
+    }
+
+    /// Recover the underlying output
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write> ResultWriter for ZoneWriter<W> {
+    fn write_result(&mut self, result: &LookupResult) -> Result<()> {
+        if result.status != LookupStatus::Success || result.hostnames.is_empty() {
+            return Ok(());
+        }
+        let Ok(ip) = result.ip.parse::<IpAddr>() else {
+            return Ok(());
+        };
+
+        let (network, origin, owner) = reverse_zone(ip);
+        let zone = self.zones.entry(network).or_insert_with(|| Zone {
+            origin,
+            ..Zone::default()
+        });
+        zone.records.insert(
+            ip,
+            Record {
+                owner,
+                names: result.hostnames.clone(),
+                ttl: result.ttl,
+            },
+        );
+        Ok(())
+    }
+
+    fn finish(&mut self, _metadata: &JsonMetadata) -> Result<()> {
+        for (i, zone) in self.zones.values().enumerate() {
+            if i > 0 {
+                writeln!(self.out)?;
+            }
+            let ttl = zone.ttl();
+            writeln!(self.out, "$ORIGIN {}", zone.origin)?;
+            writeln!(self.out, "$TTL {}", ttl)?;
+            for record in zone.records.values() {
+                let record_ttl = match record.ttl {
+                    Some(t) if t != ttl => format!("\t{}", t),
+                    _ => String::new(),
+                };
+                for name in &record.names {
+                    writeln!(
+                        self.out,
+                        "{}{}\tIN\tPTR\t{}.",
+                        record.owner, record_ttl, name
+                    )?;
+                }
+            }
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Network of the reverse zone holding `ip`, the zone's origin, and the
+/// owner name of `ip` relative to it
+pub fn reverse_zone(ip: IpAddr) -> (IpAddr, String, String) {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, c, d] = v4.octets();
+            (
+                IpAddr::V4(Ipv4Addr::new(a, b, c, 0)),
+                format!("{}.{}.{}.in-addr.arpa.", c, b, a),
+                d.to_string(),
+            )
+        }
+        IpAddr::V6(v6) => {
+            let nibbles: Vec<String> = v6
+                .octets()
+                .iter()
+                .flat_map(|byte| [byte >> 4, byte & 0xf])
+                .map(|nibble| format!("{:x}", nibble))
+                .rev()
+                .collect();
+            let network = u128::from(v6) & !u128::from(u64::MAX);
+            (
+                IpAddr::V6(Ipv6Addr::from(network)),
+                format!("{}.ip6.arpa.", nibbles[16..].join(".")),
+                nibbles[..16].join("."),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::summary::RunSummary;
+
+    fn result(ip: &str, hostnames: &[&str], ttl: Option<u32>) -> LookupResult {
+        let status = if hostnames.is_empty() {
+            LookupStatus::NxDomain
+        } else {
+            LookupStatus::Success
+        };
+        LookupResult {
+            ttl,
+            latency_ms: 5,
+            ..LookupResult::new(ip, status, "192.0.2.53:53/udp").with_hostnames(hostnames.to_vec())
+        }
+    }
+
+    #[test]
+    fn test_reverse_zone() {
+        let (network, origin, owner) = reverse_zone("192.168.1.10".parse().unwrap());
+        assert_eq!(network, "192.168.1.0".parse::<IpAddr>().unwrap());
+        assert_eq!(origin, "1.168.192.in-addr.arpa.");
+        assert_eq!(owner, "10");
+
+        let (network, origin, owner) = reverse_zone("2001:db8::1".parse().unwrap());
+        assert_eq!(network, "2001:db8::".parse::<IpAddr>().unwrap());
+        assert_eq!(origin, "0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa.");
+        assert_eq!(owner, "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0");
+    }
+
+    #[test]
+    fn test_zones_grouped_and_sorted() {
+        let mut writer = ZoneWriter::new(Vec::new());
+        for result in [
+            result("192.168.2.5", &["e.example"], Some(600)),
+            result("192.168.1.20", &["b.example", "c.example"], Some(3600)),
+            result("192.168.1.3", &["a.example"], Some(300)),
+            result("192.168.1.4", &[], None),
+        ] {
+            writer.write_result(&result).unwrap();
+        }
+        writer.finish(&RunSummary::new().to_metadata(0)).unwrap();
+
+        let text = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(
+            text,
+            "$ORIGIN 1.168.192.in-addr.arpa.\n\
+             $TTL 300\n\
+             3\tIN\tPTR\ta.example.\n\
+             20\t3600\tIN\tPTR\tb.example.\n\
+             20\t3600\tIN\tPTR\tc.example.\n\
+             \n\
+             $ORIGIN 2.168.192.in-addr.arpa.\n\
+             $TTL 600\n\
+             5\tIN\tPTR\te.example.\n"
+        );
+    }
+}
@@ -0,0 +1,171 @@
+//! Streaming lookup pipeline
+//!
+//! Addresses are pulled lazily from the input, looked up with bounded
+//! concurrency and handed to a [`ResultWriter`] as soon as each lookup
+//! completes, so memory use is independent of the input size.
+
+use crate::checkpoint::{Checkpoint, CompletedSet};
+use crate::dns::{DnsResolver, LookupResult};
+use crate::error::Result;
+use crate::output::{ResultWriter, RunSummary};
+use futures::{future, stream, StreamExt};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Tuning knobs for [`run_pipeline`]
+#[derive(Debug, Clone, Default)]
+pub struct PipelineOptions {
+    /// Maximum number of lookups in flight
+    pub concurrency: usize,
+    /// Input positions completed by a previous run, which are skipped
+    pub completed: Option<Arc<CompletedSet>>,
+    /// Once set, no new lookups are started; in-flight lookups still finish
+    pub shutdown: Option<Arc<AtomicBool>>,
+}
+
+/// Result of a pipeline run
+#[derive(Debug, Clone)]
+pub struct PipelineOutcome {
+    /// Totals over the results produced by this run
+    pub summary: RunSummary,
+    /// Whether the run stopped early because shutdown was requested
+    pub interrupted: bool,
+}
+
+/// Look up every address and stream the results into `writer`.
+///
+/// Each result is also recorded in `checkpoint` (when given) before
+/// `on_result` is called, e.g. to advance a progress bar. The writer is not
+/// finished; callers do that once they know the total run time. An input
+/// error aborts the run.
+pub async fn run_pipeline<I, F>(
+    resolver: &DnsResolver,
+    addresses: I,
+    options: &PipelineOptions,
+    writer: &mut dyn ResultWriter,
+    mut checkpoint: Option<&mut Checkpoint>,
+    mut on_result: F,
+) -> Result<PipelineOutcome>
+where
+    I: Iterator<Item = Result<IpAddr>>,
+    F: FnMut(&LookupResult),
+{
+    let completed = options.completed.clone();
+    let shutdown = options.shutdown.clone();
+    let stopping = move || shutdown.as_ref().is_some_and(|s| s.load(Ordering::SeqCst));
+
+    let pending = addresses
+        .enumerate()
+        .map(|(index, address)| (index as u64, address))
+        .filter(move |(index, _)| !completed.as_ref().is_some_and(|c| c.contains(*index)));
+
+    let stop_check = stopping.clone();
+    let mut lookups = stream::iter(pending)
+        .take_while(move |_| future::ready(!stop_check()))
+        .map(|(index, address)| async move {
+            let ip = address?;
+            let result = resolver.lookup(&ip.to_string()).await?;
+            Ok::<_, crate::error::ReverDNSError>((index, result))
+        })
+        .buffer_unordered(options.concurrency.max(1));
+
+    let mut summary = RunSummary::new();
+    while let Some(item) = lookups.next().await {
+        let (index, result) = item?;
+        writer.write_result(&result)?;
+        if let Some(checkpoint) = checkpoint.as_deref_mut() {
+            checkpoint.record(index, &result)?;
+        }
+        summary.record(&result);
+        on_result(&result);
+    }
+
+    if let Some(checkpoint) = checkpoint {
+        checkpoint.sync()?;
+    }
+
+    Ok(PipelineOutcome {
+        summary,
+        interrupted: stopping(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::NdjsonWriter;
+    use crate::ReverDNSError;
+
+    #[tokio::test]
+    async fn test_pipeline_aborts_on_input_error() {
+        let resolver = DnsResolver::new(1, 0, 0).await.unwrap();
+        let options = PipelineOptions {
+            concurrency: 2,
+            ..Default::default()
+        };
+        let addresses = vec![Err(ReverDNSError::ConfigError("bad input".to_string()))];
+        let mut writer = NdjsonWriter::new(Vec::new());
+
+        let result = run_pipeline(
+            &resolver,
+            addresses.into_iter(),
+            &options,
+            &mut writer,
+            None,
+            |_| {},
+        )
+        .await;
+        assert!(result.is_err());
+        assert!(writer.into_inner().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_skips_completed_and_stops_on_shutdown() {
+        let resolver = DnsResolver::new(1, 0, 0).await.unwrap();
+        let mut completed = CompletedSet::new();
+        completed.insert(0);
+        completed.insert(1);
+        let options = PipelineOptions {
+            concurrency: 1,
+            completed: Some(Arc::new(completed)),
+            shutdown: Some(Arc::new(AtomicBool::new(true))),
+        };
+        let addresses = ["192.0.2.1", "192.0.2.2"]
+            .iter()
+            .map(|ip| Ok(ip.parse::<IpAddr>().unwrap()));
+        let mut writer = NdjsonWriter::new(Vec::new());
+
+        let outcome = run_pipeline(&resolver, addresses, &options, &mut writer, None, |_| {})
+            .await
+            .unwrap();
+        assert_eq!(outcome.summary.total, 0);
+        assert!(outcome.interrupted);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_enforces_global_rate() {
+        use crate::dns::RateLimiter;
+        use std::time::{Duration, Instant};
+
+        // Loopback PTR lookups are answered locally, so only the limiter
+        // determines how long the run takes
+        let resolver = DnsResolver::new(1, 0, 0)
+            .await
+            .unwrap()
+            .with_rate_limiter(Arc::new(RateLimiter::new(20, 1)), None);
+        let options = PipelineOptions {
+            concurrency: 8,
+            ..Default::default()
+        };
+        let addresses = (1..=6).map(|n| Ok(IpAddr::from([127, 0, 0, n])));
+        let mut writer = NdjsonWriter::new(Vec::new());
+
+        let start = Instant::now();
+        let outcome = run_pipeline(&resolver, addresses, &options, &mut writer, None, |_| {})
+            .await
+            .unwrap();
+        assert_eq!(outcome.summary.total, 6);
+        assert!(start.elapsed() >= Duration::from_millis(240));
+    }
+}
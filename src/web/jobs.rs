@@ -0,0 +1,210 @@
+use crate::output::json::JsonOutput;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// How long finished jobs are kept around for polling
+const JOB_RETENTION_SECS: i64 = 3600;
+
+/// Maximum number of pending or processing jobs
+pub const MAX_OUTSTANDING_JOBS: usize = 64;
+
+/// Lifecycle state of a lookup job
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Pending,
+    Processing,
+    Completed,
+    Failed,
+}
+
+impl JobStatus {
+    /// Whether the job has reached a terminal state
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed)
+    }
+}
+
+impl std::fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobStatus::Pending => write!(f, "pending"),
+            JobStatus::Processing => write!(f, "processing"),
+            JobStatus::Completed => write!(f, "completed"),
+            JobStatus::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+/// A submitted lookup job and, once finished, its results
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub job_id: Uuid,
+    pub status: JobStatus,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+    pub total_ips: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub output: Option<JsonOutput>,
+}
+
+impl Job {
+    fn new(total_ips: usize) -> Self {
+        Self {
+            job_id: Uuid::new_v4(),
+            status: JobStatus::Pending,
+            created_at: Utc::now(),
+            completed_at: None,
+            total_ips,
+            error: None,
+            output: None,
+        }
+    }
+}
+
+/// Shared in-memory job registry
+#[derive(Debug, Clone, Default)]
+pub struct JobStore {
+    jobs: Arc<RwLock<HashMap<Uuid, Job>>>,
+}
+
+impl JobStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a new pending job and return a snapshot of it, or `None` if
+    /// [`MAX_OUTSTANDING_JOBS`] jobs are still unfinished
+    pub async fn create(&self, total_ips: usize) -> Option<Job> {
+        let mut jobs = self.jobs.write().await;
+        Self::prune_expired(&mut jobs);
+        let outstanding = jobs.values().filter(|j| !j.status.is_finished()).count();
+        if outstanding >= MAX_OUTSTANDING_JOBS {
+            return None;
+        }
+        let job = Job::new(total_ips);
+        jobs.insert(job.job_id, job.clone());
+        Some(job)
+    }
+
+    /// Get a snapshot of a job
+    pub async fn get(&self, job_id: &Uuid) -> Option<Job> {
+        self.jobs.read().await.get(job_id).cloned()
+    }
+
+    /// Mark a job as picked up by a worker
+    pub async fn mark_processing(&self, job_id: &Uuid) {
+        if let Some(job) = self.jobs.write().await.get_mut(job_id) {
+            job.status = JobStatus::Processing;
+        }
+    }
+
+    /// Store the results of a successfully finished job
+    pub async fn complete(&self, job_id: &Uuid, output: JsonOutput) {
+        if let Some(job) = self.jobs.write().await.get_mut(job_id) {
+            job.status = JobStatus::Completed;
+            job.completed_at = Some(Utc::now());
+            job.output = Some(output);
+        }
+    }
+
+    /// Record that a job could not be finished
+    pub async fn fail(&self, job_id: &Uuid, error: String) {
+        if let Some(job) = self.jobs.write().await.get_mut(job_id) {
+            job.status = JobStatus::Failed;
+            job.completed_at = Some(Utc::now());
+            job.error = Some(error);
+        }
+    }
+
+    /// Number of jobs currently tracked
+    pub async fn len(&self) -> usize {
+        self.jobs.read().await.len()
+    }
+
+    /// Whether no jobs are tracked
+    pub async fn is_empty(&self) -> bool {
+        self.jobs.read().await.is_empty()
+    }
+
+    /// Drop finished jobs older than the retention period
+    pub async fn prune(&self) {
+        Self::prune_expired(&mut *self.jobs.write().await);
+    }
+
+    fn prune_expired(jobs: &mut HashMap<Uuid, Job>) {
+        let cutoff = Utc::now() - Duration::seconds(JOB_RETENTION_SECS);
+        jobs.retain(|_, job| job.completed_at.is_none_or(|done| done > cutoff));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_job_lifecycle() {
+        let store = JobStore::new();
+        let job = store.create(2).await.unwrap();
+        assert_eq!(job.status, JobStatus::Pending);
+
+        store.mark_processing(&job.job_id).await;
+        assert_eq!(
+            store.get(&job.job_id).await.unwrap().status,
+            JobStatus::Processing
+        );
+
+        store.fail(&job.job_id, "boom".to_string()).await;
+        let job = store.get(&job.job_id).await.unwrap();
+        assert_eq!(job.status, JobStatus::Failed);
+        assert!(job.status.is_finished());
+        assert_eq!(job.error.as_deref(), Some("boom"));
+    }
+
+    #[tokio::test]
+    async fn test_outstanding_job_cap() {
+        let store = JobStore::new();
+        let mut ids = Vec::new();
+        for _ in 0..MAX_OUTSTANDING_JOBS {
+            ids.push(store.create(1).await.unwrap().job_id);
+        }
+        assert!(store.create(1).await.is_none());
+
+        // Finished jobs no longer count
+        store.fail(&ids[0], "boom".to_string()).await;
+        assert!(store.create(1).await.is_some());
+        assert!(store.create(1).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_prune_expired() {
+        let store = JobStore::new();
+        let job = store.create(1).await.unwrap();
+        store.fail(&job.job_id, "boom".to_string()).await;
+        store.prune().await;
+        assert_eq!(store.len().await, 1);
+
+        store
+            .jobs
+            .write()
+            .await
+            .get_mut(&job.job_id)
+            .unwrap()
+            .completed_at = Some(Utc::now() - Duration::seconds(JOB_RETENTION_SECS + 1));
+        store.prune().await;
+        assert!(store.is_empty().await);
+    }
+
+    #[test]
+    fn test_job_status_display() {
+        assert_eq!(JobStatus::Pending.to_string(), "pending");
+        assert_eq!(JobStatus::Completed.to_string(), "completed");
+    }
+}
@@ -0,0 +1,5 @@
+pub mod jobs;
+pub mod server;
+
+pub use jobs::{Job, JobStatus, JobStore};
+pub use server::{routes, serve, AppState};
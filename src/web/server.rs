@@ -0,0 +1,436 @@
+use crate::dns::DnsResolver;
+use crate::error::{Result, ReverDNSError};
+use crate::output::build_json_output;
+use crate::web::jobs::{JobStatus, JobStore, MAX_OUTSTANDING_JOBS};
+use chrono::{DateTime, Utc};
+use futures::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+use uuid::Uuid;
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply};
+
+/// Maximum number of IPs accepted in a single job
+pub const MAX_IPS_PER_JOB: usize = 10_000;
+
+/// Maximum accepted request body size in bytes
+const MAX_BODY_BYTES: u64 = 1024 * 1024;
+
+/// How often expired jobs are dropped
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Shared state of the web API server
+#[derive(Clone)]
+pub struct AppState {
+    pub resolver: Arc<DnsResolver>,
+    pub jobs: JobStore,
+    /// Upper bound for per-job concurrency
+    pub max_concurrency: usize,
+    /// Per-query timeout used when a job does not specify one, and its upper
+    /// bound
+    pub default_timeout: u64,
+    started_at: Instant,
+}
+
+impl AppState {
+    pub fn new(resolver: DnsResolver, max_concurrency: usize, default_timeout: u64) -> Self {
+        Self {
+            resolver: Arc::new(resolver),
+            jobs: JobStore::new(),
+            max_concurrency,
+            default_timeout,
+            started_at: Instant::now(),
+        }
+    }
+}
+
+/// Body of `POST /api/lookup`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LookupRequest {
+    pub ips: Vec<String>,
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    #[serde(default)]
+    pub concurrency: Option<usize>,
+}
+
+/// Response of `POST /api/lookup`
+#[derive(Debug, Serialize)]
+pub struct JobAccepted {
+    pub job_id: Uuid,
+    pub status: JobStatus,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Response of `GET /api/health`
+#[derive(Debug, Serialize)]
+pub struct HealthResponse {
+    pub status: &'static str,
+    pub version: &'static str,
+    pub uptime_seconds: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+/// Validated parameters of a job
+struct JobSpec {
+    ips: Vec<String>,
+    timeout: Duration,
+    concurrency: usize,
+}
+
+impl LookupRequest {
+    fn into_spec(self, state: &AppState) -> Result<JobSpec> {
+        if let Some(format) = &self.format {
+            if !format.eq_ignore_ascii_case("json") {
+                return Err(ReverDNSError::InvalidFormat(format.clone()));
+            }
+        }
+
+        if self.ips.is_empty() {
+            return Err(ReverDNSError::ConfigError("No IPs provided".to_string()));
+        }
+        if self.ips.len() > MAX_IPS_PER_JOB {
+            return Err(ReverDNSError::ConfigError(format!(
+                "Too many IPs in one job ({} > {})",
+                self.ips.len(),
+                MAX_IPS_PER_JOB
+            )));
+        }
+
+        let mut ips = Vec::with_capacity(self.ips.len());
+        for ip in self.ips {
+            let trimmed = ip.trim();
+            IpAddr::from_str(trimmed)
+                .map_err(|_| ReverDNSError::InvalidIpAddress(ip.to_string()))?;
+            ips.push(trimmed.to_string());
+        }
+
+        let timeout = match self.timeout {
+            Some(0) => {
+                return Err(ReverDNSError::ConfigError(
+                    "Timeout must be greater than 0".to_string(),
+                ))
+            }
+            Some(secs) => secs.min(state.default_timeout),
+            None => state.default_timeout,
+        };
+
+        let concurrency = match self.concurrency {
+            Some(0) => {
+                return Err(ReverDNSError::ConfigError(
+                    "Concurrency must be greater than 0".to_string(),
+                ))
+            }
+            Some(n) => n.min(state.max_concurrency),
+            None => state.max_concurrency,
+        };
+
+        Ok(JobSpec {
+            ips,
+            timeout: Duration::from_secs(timeout),
+            concurrency,
+        })
+    }
+}
+
+/// Build all API routes
+pub fn routes(state: AppState) -> impl Filter<Extract = impl Reply, Error = Infallible> + Clone {
+    let with_state = warp::any().map(move || state.clone());
+
+    let health = warp::path!("api" / "health")
+        .and(warp::get())
+        .and(with_state.clone())
+        .and_then(handle_health);
+
+    let submit = warp::path!("api" / "lookup")
+        .and(warp::post())
+        .and(warp::body::content_length_limit(MAX_BODY_BYTES))
+        .and(warp::body::json())
+        .and(with_state.clone())
+        .and_then(handle_submit);
+
+    let status = warp::path!("api" / "lookup" / String)
+        .and(warp::get())
+        .and(with_state)
+        .and_then(handle_status);
+
+    health
+        .or(submit)
+        .or(status)
+        .recover(handle_rejection)
+        .with(warp::trace::request())
+}
+
+/// Run the web API server on `addr` until Ctrl-C is received, dropping
+/// expired jobs along the way
+pub async fn serve(state: AppState, addr: SocketAddr) -> Result<()> {
+    let jobs = state.jobs.clone();
+    let (bound, server) = warp::serve(routes(state))
+        .try_bind_with_graceful_shutdown(addr, async {
+            let _ = tokio::signal::ctrl_c().await;
+            info!("Shutdown signal received, stopping web server");
+        })
+        .map_err(|e| ReverDNSError::NetworkError(format!("Failed to bind {}: {}", addr, e)))?;
+
+    info!("Web API listening on http://{}", bound);
+    let pruner = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            jobs.prune().await;
+        }
+    });
+    server.await;
+    pruner.abort();
+    Ok(())
+}
+
+async fn handle_health(state: AppState) -> std::result::Result<impl Reply, Infallible> {
+    Ok(warp::reply::json(&HealthResponse {
+        status: "healthy",
+        version: env!("CARGO_PKG_VERSION"),
+        uptime_seconds: state.started_at.elapsed().as_secs(),
+    }))
+}
+
+async fn handle_submit(
+    request: LookupRequest,
+    state: AppState,
+) -> std::result::Result<warp::reply::Response, Infallible> {
+    let spec = match request.into_spec(&state) {
+        Ok(spec) => spec,
+        Err(e) => return Ok(error_reply(StatusCode::BAD_REQUEST, e.to_string())),
+    };
+
+    let Some(job) = state.jobs.create(spec.ips.len()).await else {
+        return Ok(error_reply(
+            StatusCode::TOO_MANY_REQUESTS,
+            format!(
+                "Too many unfinished jobs (at most {}), try again later",
+                MAX_OUTSTANDING_JOBS
+            ),
+        ));
+    };
+    info!("Accepted job {} with {} IPs", job.job_id, spec.ips.len());
+    tokio::spawn(run_job(state, job.job_id, spec));
+
+    let accepted = JobAccepted {
+        job_id: job.job_id,
+        status: job.status,
+        created_at: job.created_at,
+    };
+    Ok(
+        warp::reply::with_status(warp::reply::json(&accepted), StatusCode::ACCEPTED)
+            .into_response(),
+    )
+}
+
+async fn handle_status(
+    job_id: String,
+    state: AppState,
+) -> std::result::Result<warp::reply::Response, Infallible> {
+    let job_id = match Uuid::parse_str(&job_id) {
+        Ok(id) => id,
+        Err(_) => {
+            return Ok(error_reply(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid job id: {}", job_id),
+            ))
+        }
+    };
+
+    match state.jobs.get(&job_id).await {
+        Some(job) => Ok(warp::reply::json(&job).into_response()),
+        None => Ok(error_reply(
+            StatusCode::NOT_FOUND,
+            format!("Job not found: {}", job_id),
+        )),
+    }
+}
+
+async fn handle_rejection(
+    err: Rejection,
+) -> std::result::Result<warp::reply::Response, Infallible> {
+    let (code, message) = if err.is_not_found() {
+        (StatusCode::NOT_FOUND, "Not found".to_string())
+    } else if let Some(e) = err.find::<warp::filters::body::BodyDeserializeError>() {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid request body: {}", e),
+        )
+    } else if err.find::<warp::reject::PayloadTooLarge>().is_some() {
+        (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "Request body too large".to_string(),
+        )
+    } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
+        (
+            StatusCode::METHOD_NOT_ALLOWED,
+            "Method not allowed".to_string(),
+        )
+    } else {
+        error!("Unhandled rejection: {:?}", err);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Internal server error".to_string(),
+        )
+    };
+
+    Ok(error_reply(code, message))
+}
+
+fn error_reply(code: StatusCode, error: String) -> warp::reply::Response {
+    warp::reply::with_status(warp::reply::json(&ErrorResponse { error }), code).into_response()
+}
+
+async fn run_job(state: AppState, job_id: Uuid, spec: JobSpec) {
+    let start = Instant::now();
+    state.jobs.mark_processing(&job_id).await;
+
+    let resolver = &state.resolver;
+    let timeout = spec.timeout;
+    let results = stream::iter(spec.ips)
+        .map(|ip| async move { resolver.lookup_with_timeout(&ip, timeout).await })
+        .buffer_unordered(spec.concurrency)
+        .collect::<Vec<_>>()
+        .await;
+
+    match results.into_iter().collect::<Result<Vec<_>>>() {
+        Ok(results) => {
+            let elapsed = start.elapsed().as_millis();
+            info!("Job {} completed in {}ms", job_id, elapsed);
+            state
+                .jobs
+                .complete(&job_id, build_json_output(&results, elapsed))
+                .await;
+        }
+        Err(e) => {
+            error!("Job {} failed: {}", job_id, e);
+            state.jobs.fail(&job_id, e.to_string()).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    async fn test_state() -> AppState {
+        let resolver = DnsResolver::new(1, 0, 0).await.unwrap();
+        AppState::new(resolver, 4, 1)
+    }
+
+    #[tokio::test]
+    async fn test_health() {
+        let api = routes(test_state().await);
+        let res = warp::test::request()
+            .method("GET")
+            .path("/api/health")
+            .reply(&api)
+            .await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(body["status"], "healthy");
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+    }
+
+    #[tokio::test]
+    async fn test_submit_and_poll_job() {
+        let state = test_state().await;
+        let api = routes(state.clone());
+        let res = warp::test::request()
+            .method("POST")
+            .path("/api/lookup")
+            .json(&serde_json::json!({ "ips": ["192.0.2.1"], "format": "json" }))
+            .reply(&api)
+            .await;
+
+        assert_eq!(res.status(), StatusCode::ACCEPTED);
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        let job_id = body["job_id"].as_str().unwrap().to_string();
+
+        let res = warp::test::request()
+            .method("GET")
+            .path(&format!("/api/lookup/{}", job_id))
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(body["job_id"], job_id);
+        assert_eq!(body["total_ips"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_submit_rejects_invalid_input() {
+        let api = routes(test_state().await);
+
+        for payload in [
+            serde_json::json!({ "ips": [] }),
+            serde_json::json!({ "ips": ["not-an-ip"] }),
+            serde_json::json!({ "ips": ["8.8.8.8"], "format": "xml" }),
+            serde_json::json!({ "ips": ["8.8.8.8"], "concurrency": 0 }),
+            serde_json::json!({ "addresses": ["8.8.8.8"] }),
+        ] {
+            let res = warp::test::request()
+                .method("POST")
+                .path("/api/lookup")
+                .json(&payload)
+                .reply(&api)
+                .await;
+            assert_eq!(
+                res.status(),
+                StatusCode::BAD_REQUEST,
+                "payload: {}",
+                payload
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_submit_past_job_cap() {
+        let state = test_state().await;
+        for _ in 0..MAX_OUTSTANDING_JOBS {
+            state.jobs.create(1).await.unwrap();
+        }
+        let res = warp::test::request()
+            .method("POST")
+            .path("/api/lookup")
+            .json(&serde_json::json!({ "ips": ["192.0.2.1"] }))
+            .reply(&routes(state))
+            .await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_job() {
+        let api = routes(test_state().await);
+
+        let res = warp::test::request()
+            .method("GET")
+            .path(&format!("/api/lookup/{}", Uuid::new_v4()))
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let res = warp::test::request()
+            .method("GET")
+            .path("/api/lookup/not-a-uuid")
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}